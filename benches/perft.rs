@@ -0,0 +1,32 @@
+// Measures move generation speed with perft, run with `cargo bench --bench perft`.
+// Start position has few captures and no castling early on, so Kiwipete is measured as well
+use rusty_chess_engine::movegen::perft::perft;
+use rusty_chess_engine::utils::fen::{Fen, DEFAULT_FEN};
+
+use std::time::{Duration, Instant};
+
+const KIWIPETE_FEN: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITIONS: [(&str, &str, usize); 2] = [
+    ("start position", DEFAULT_FEN, 4),
+    ("kiwipete", KIWIPETE_FEN, 3),
+];
+const RUNS: u32 = 5;
+
+fn main() {
+    for (name, fen, depth) in POSITIONS {
+        let game = Fen(fen.to_string()).process().unwrap();
+
+        // First run also pays for the lazy attack tables initialization
+        let nodes = perft(&game, depth);
+
+        let mut best = Duration::MAX;
+        for _ in 0..RUNS {
+            let start = Instant::now();
+            assert_eq!(perft(&game, depth), nodes);
+            best = best.min(start.elapsed());
+        }
+
+        let nps = nodes as f64 / best.as_secs_f64();
+        println!("perft({}) {}: {} nodes, best of {} runs {:.3?}, {:.0} nodes/s", depth, name, nodes, RUNS, best, nps);
+    }
+}
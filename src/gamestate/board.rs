@@ -1,5 +1,9 @@
-use rayon::prelude::*;
 use crate::gamestate::defs::*;
+use crate::movegen::{
+    masks::{KNIGHT_ATTACKS_MASKS, KING_ATTAKS_MASKS, WHITE_PAWN_ATTACKS_MASKS, BLACK_PAWN_ATTACKS_MASKS},
+    rook_attacks,
+    bishop_attacks,
+};
 
 
 pub type Bitboard = u64;
@@ -31,6 +35,15 @@ pub enum Side {
     White,
     Black,
 }
+impl Side {
+    #[inline(always)]
+    pub const fn opposite(&self) -> Side {
+        match self {
+            Side::White => Side::Black,
+            Side::Black => Side::White,
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Board {
@@ -74,20 +87,81 @@ impl Board {
             Side::Black => self.black_pieces[pt as usize] &= piece_mask,
         }
     }
+    #[inline(always)]
+    pub fn move_piece(&mut self, from: Square, to: Square, pt: PieceType, side: Side) {
+        self.remove_piece_at_square(from, pt, side);
+        self.place_piece_at_square(to, pt, side);
+    }
     // Clears the square for any piece and side
     pub fn clear_square(&mut self, square: Square) {
         let piece_mask = !square.get_mask();
         // Clear the square for white pieces
-        self.white_pieces.par_iter_mut().for_each(|i| {
+        self.white_pieces.iter_mut().for_each(|i| {
             *i &= piece_mask;
         });
 
         // Clear the square for black pieces
-        self.black_pieces.par_iter_mut().for_each(|i| {
+        self.black_pieces.iter_mut().for_each(|i| {
             *i &= piece_mask;
         });
     }
-    
+    #[inline(always)]
+    pub fn get_pieces(&self, side: Side) -> &[Bitboard; PIECE_TYPES_NUM] {
+        match side {
+            Side::White => &self.white_pieces,
+            Side::Black => &self.black_pieces,
+        }
+    }
+    #[inline(always)]
+    pub fn get_occupancy(&self, side: Side) -> Bitboard {
+        self.get_pieces(side).iter().fold(0, |acc, &piece| acc | piece)
+    }
+    #[inline(always)]
+    pub fn get_combined_occupancy(&self) -> Bitboard {
+        self.get_occupancy(Side::White) | self.get_occupancy(Side::Black)
+    }
+    #[inline(always)]
+    pub fn get_king_square(&self, side: Side) -> Option<Square> {
+        let king = self.get_pieces(side)[PieceType::King as usize];
+        if king == 0 {
+            return None;
+        }
+        Some(Square::new(king.trailing_zeros() as u8))
+    }
+
+    // Attack queries take occupancy separately from the board, so they can answer for a position that is slightly different
+    // from the current one, e.g. with the king removed to see the squares behind it, or with a piece already captured.
+    // Only pieces that are in the given occupancy are returned as attackers.
+
+    // Pieces of the given side that attack the square
+    #[inline(always)]
+    pub fn attackers_of_side(&self, square: Square, side: Side, occupancy: Bitboard) -> Bitboard {
+        let (pieces, pawn_attacks_masks) = match side {
+            // Pawn attacking the square stands where the opposite colored pawn from the square would attack
+            Side::White => (&self.white_pieces, &BLACK_PAWN_ATTACKS_MASKS),
+            Side::Black => (&self.black_pieces, &WHITE_PAWN_ATTACKS_MASKS),
+        };
+        let index = square.get_index();
+        let rooks_queens = pieces[PieceType::Rook as usize] | pieces[PieceType::Queen as usize];
+        let bishops_queens = pieces[PieceType::Bishop as usize] | pieces[PieceType::Queen as usize];
+
+        let attackers = (pawn_attacks_masks[index] & pieces[PieceType::Pawn as usize])
+            | (KNIGHT_ATTACKS_MASKS[index] & pieces[PieceType::Knight as usize])
+            | (KING_ATTAKS_MASKS[index] & pieces[PieceType::King as usize])
+            | (rook_attacks(square, occupancy) & rooks_queens)
+            | (bishop_attacks(square, occupancy) & bishops_queens);
+
+        attackers & occupancy
+    }
+    // Pieces of both sides that attack the square
+    #[inline(always)]
+    pub fn attackers_to(&self, square: Square, occupancy: Bitboard) -> Bitboard {
+        self.attackers_of_side(square, Side::White, occupancy) | self.attackers_of_side(square, Side::Black, occupancy)
+    }
+    #[inline(always)]
+    pub fn is_square_attacked(&self, square: Square, by_side: Side, occupancy: Bitboard) -> bool {
+        self.attackers_of_side(square, by_side, occupancy) != 0
+    }
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Square(u8);
@@ -181,15 +255,37 @@ impl Square {
     
     #[inline(always)]
     pub fn get_squares_from_bitboard(bitboard: Bitboard) -> Vec<Square> {
-        (0..64)
-            .into_par_iter()
-            .filter_map(|i| {
-                if (bitboard & (1u64 << i)) != 0 {
-                    Some(Square::new(i as u8))
-                } else {
-                    None
-                }
-            })
-            .collect()
+        BitboardIter::new(bitboard).collect()
+    }
+}
+
+// Iterates over the squares of the set bits, every step pops the least significant one.
+// Check https://www.chessprogramming.org/Bitboard_Serialization for details
+#[derive(Debug, Clone, Copy)]
+pub struct BitboardIter(Bitboard);
+impl BitboardIter {
+    #[inline(always)]
+    pub const fn new(bitboard: Bitboard) -> Self {
+        BitboardIter(bitboard)
+    }
+}
+impl Iterator for BitboardIter {
+    type Item = Square;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = Square::new(self.0.trailing_zeros() as u8);
+        // Clears the least significant bit
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count_ones() as usize;
+        (count, Some(count))
     }
 }
+impl ExactSizeIterator for BitboardIter {}
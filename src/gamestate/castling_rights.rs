@@ -20,6 +20,14 @@ impl CastlingSide {
             _ => unreachable!("Invalid castling side from u8 call")
         }
     }
+    #[inline(always)]
+    pub fn has_kingside(&self) -> bool {
+        matches!(self, CastlingSide::Kingside | CastlingSide::Both)
+    }
+    #[inline(always)]
+    pub fn has_queenside(&self) -> bool {
+        matches!(self, CastlingSide::Queenside | CastlingSide::Both)
+    }
 }
 
 // structure is as follows: 0000(garbage bits), 00(black bits), 00(white bits) -> 0000****
@@ -48,7 +56,7 @@ impl CastlingRights {
 
         match side {
             Side::White => CastlingSide::from_u8(self.0 & WHITE_MASK),
-            Side::Black => CastlingSide::from_u8(self.0 & BLACK_MASK >> BLACK_SIDE_OFFSET),
+            Side::Black => CastlingSide::from_u8((self.0 & BLACK_MASK) >> BLACK_SIDE_OFFSET),
         }
     }
     // If castling occures rules should be completely disabled for the side that castled
@@ -2,4 +2,6 @@ pub const PIECE_TYPES_NUM: usize = 6;
 pub const BOARD_SIDE_LENGTH: usize = 8;
 pub const BOARD_NUM_OF_SQUARES: usize = 64;
 pub const SIDE_NUM: usize = 2;
-pub const CASTLING_CONFIGURATIONS_NUM: usize = 16;
\ No newline at end of file
+pub const CASTLING_CONFIGURATIONS_NUM: usize = 16;
+// Legal games give at most 10 pieces of the same type, but FEN can describe anything that fits on the board
+pub const MAX_PIECES_OF_TYPE: usize = BOARD_NUM_OF_SQUARES;
@@ -1,7 +1,17 @@
 use crate::gamestate::*;
 
+// Only the key and the clock are needed to detect repetitions, so the whole position is not kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HistoryEntry {
+    zobrist_key: u64,
+    half_move_clock: u8,
+}
+
+// Record of all positions of the game, the last one being the current position. Positions are pushed after making
+// a move and popped after unmaking it, so the same record works for the played game and for the search on top of it
+#[derive(Debug, Clone, Default)]
 pub struct History {
-    list: Vec<Gamestate>
+    list: Vec<HistoryEntry>
 }
 impl History {
     pub fn new() -> Self {
@@ -9,13 +19,47 @@ impl History {
             list: vec![]
         }
     }
-    pub fn push(&mut self, game:Gamestate) {
-        self.list.push(game);
+    pub fn push(&mut self, game: &Gamestate) {
+        self.list.push(HistoryEntry {
+            zobrist_key: game.zobrist_key,
+            half_move_clock: game.half_move_clock,
+        });
     }
     pub fn pop(&mut self) {
         self.list.pop();
     }
-    pub fn get_last(&self) -> &Gamestate {
-        &self.list[self.list.len() - 1]
+    // Key of the current position
+    pub fn get_last(&self) -> Option<u64> {
+        self.list.last().map(|entry| entry.zobrist_key)
+    }
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
     }
-}
\ No newline at end of file
+    // Checks if the current position occurred at least n times, counting itself. Use 3 for threefold repetition when
+    // adjudicating the game and 2 for draw detection in search, where any repetition can be played again.
+    // Check https://www.chessprogramming.org/Repetitions for details
+    pub fn is_repetition(&self, n: usize) -> bool {
+        let Some(current) = self.list.last() else {
+            return false;
+        };
+        let current_index = self.list.len() - 1;
+        // Positions before the last capture or pawn move can not appear again, so there is no need to look further back
+        let plies_back = (current.half_move_clock as usize).min(current_index);
+
+        let mut occurrences = 1;
+        // Same position needs the same side to move, so only every second ply is checked, starting from 4 plies back
+        // since both sides need at least two moves to get back to a position
+        for ply in (4..=plies_back).step_by(2) {
+            if self.list[current_index - ply].zobrist_key == current.zobrist_key {
+                occurrences += 1;
+                if occurrences >= n {
+                    return true;
+                }
+            }
+        }
+        occurrences >= n
+    }
+}
@@ -1,15 +1,19 @@
 pub mod board;
 pub mod castling_rights;
 pub mod zobrist;
+pub mod polyglot;
 pub mod history;
+pub mod status;
+pub mod validation;
 pub mod defs;
-
-use rayon::iter::FlatMap;
+pub mod undo;
 
 use self::{
-    board::{Board, Side, Square, PieceType, Bitboard},
-    castling_rights::CastlingRights,
+    board::{Bitboard, Board, Side, Square, PieceType},
+    castling_rights::{CastlingRights, CastlingSide},
+    undo::{UndoRecord, UndoStack},
     zobrist::*,
+    polyglot::PolyglotKeys,
 };
 
 // This constants are related to moves and their encoding
@@ -32,16 +36,20 @@ pub struct Gamestate {
     pub board: Board,
     pub side_to_move: Side,
     pub castling_rights: CastlingRights,
-    pub en_passant: u8,
+    // Square the pawn that has just made a double push passed over, same as in FEN
+    pub en_passant: Option<Square>,
     pub half_move_clock: u8,
     pub full_move_count: u8,
 
     pub zobrist_key: u64,
-
-    
+    // Keys for evaluation caches, pawn key covers pawns and kings, material key covers piece counts only
+    pub pawn_key: u64,
+    pub material_key: u64,
+    // Pieces giving check to the king of the side to move, kept up to date by the make and unmake functions
+    pub checkers: Bitboard,
 }
 impl Gamestate {
-    pub fn new(board:Board, side_to_move: Side, castling_rights: CastlingRights, en_passant: u8, half_move_clock: u8, full_move_count:u8) -> Self {
+    pub fn new(board:Board, side_to_move: Side, castling_rights: CastlingRights, en_passant: Option<Square>, half_move_clock: u8, full_move_count:u8) -> Self {
         let mut game = Gamestate {
             board, 
             side_to_move,
@@ -50,38 +58,252 @@ impl Gamestate {
             half_move_clock,
             full_move_count,
             zobrist_key: 0,
+            pawn_key: 0,
+            material_key: 0,
+            checkers: 0,
         };
         game.zobrist_key = Zobrist::hash(&game);
+        game.pawn_key = Zobrist::pawn_hash(&game);
+        game.material_key = Zobrist::material_hash(&game);
+        game.checkers = game.compute_checkers();
         game
     }
+    // Key of the position in the Polyglot scheme, the one opening books and other engines use
+    pub fn polyglot_key(&self, keys: &PolyglotKeys) -> u64 {
+        keys.hash(self)
+    }
+    #[inline(always)]
+    pub fn is_in_check(&self) -> bool {
+        self.checkers != 0
+    }
+    // Attack queries for the current occupancy, check Board for the ones with custom occupancy
+    #[inline(always)]
+    pub fn attackers_to(&self, square: Square) -> Bitboard {
+        self.board.attackers_to(square, self.board.get_combined_occupancy())
+    }
+    #[inline(always)]
+    pub fn is_square_attacked(&self, square: Square, by_side: Side) -> bool {
+        self.board.is_square_attacked(square, by_side, self.board.get_combined_occupancy())
+    }
+    // Position without the king of the side to move (e.g. in tests) has no checkers
+    fn compute_checkers(&self) -> Bitboard {
+        match self.board.get_king_square(self.side_to_move) {
+            Some(king_square) => self.board.attackers_of_side(king_square, self.side_to_move.opposite(), self.board.get_combined_occupancy()),
+            None => 0,
+        }
+    }
+    // Copy-make: the current position is left untouched and a new one with the move applied is returned
     pub fn make_move(&self, mov: &Move) -> Gamestate {
-        let new_zobrist_key = Zobrist::incremental_hash_update(self, mov);
-        let new_side_to_move = match self.side_to_move {
-            Side::White => Side::Black,
-            Side::Black => Side::White,
+        let mut game = self.clone();
+        game.zobrist_key = Zobrist::incremental_hash_update(self, mov);
+        game.pawn_key = Zobrist::incremental_pawn_hash_update(self, mov);
+        game.material_key = Zobrist::incremental_material_hash_update(self, mov);
+        game.apply_move(mov);
+        game.debug_assert_keys();
+        game
+    }
+    // Make-unmake: the move is applied in place and the irreversible state is pushed to the undo stack,
+    // so the same position can be walked thru the tree without cloning the board at every node
+    pub fn make_move_mut(&mut self, mov: &Move, undo_stack: &mut UndoStack) {
+        let captured_piece = match mov.get_flags() {
+            MoveFlags::EpCapture => Some(PieceType::Pawn),
+            _ if mov.is_capture() => self.board.get_piece_at_square(mov.get_to_square()).map(|(piece_type, _)| piece_type),
+            _ => None,
         };
+        undo_stack.push(UndoRecord {
+            captured_piece,
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            half_move_clock: self.half_move_clock,
+            full_move_count: self.full_move_count,
+            zobrist_key: self.zobrist_key,
+            pawn_key: self.pawn_key,
+            material_key: self.material_key,
+            checkers: self.checkers,
+        });
+
+        self.zobrist_key = Zobrist::incremental_hash_update(self, mov);
+        self.pawn_key = Zobrist::incremental_pawn_hash_update(self, mov);
+        self.material_key = Zobrist::incremental_material_hash_update(self, mov);
+        self.apply_move(mov);
+        self.debug_assert_keys();
+    }
+    // Incrementally updated keys have to stay equal to the ones computed from scratch, checked in debug builds only
+    #[inline(always)]
+    fn debug_assert_keys(&self) {
+        debug_assert_eq!(self.zobrist_key, Zobrist::hash(self), "Zobrist key differs from the one computed from scratch");
+        debug_assert_eq!(self.pawn_key, Zobrist::pawn_hash(self), "Pawn key differs from the one computed from scratch");
+        debug_assert_eq!(self.material_key, Zobrist::material_hash(self), "Material key differs from the one computed from scratch");
+    }
+    // Takes back the move last made with make_move_mut, the move has to be the same one
+    pub fn unmake_move(&mut self, mov: &Move, undo_stack: &mut UndoStack) {
+        let undo = undo_stack.pop().expect("Attempted to unmake a move with an empty undo stack");
+        let (flags, from, to) = mov.decode();
+        // Side that made the move
+        let side = self.side_to_move.opposite();
+        let opponent = self.side_to_move;
+
+        match flags {
+            MoveFlags::Quiet | MoveFlags::DoublePawnPush => {
+                let (piece_moved, _) = self.board.get_piece_at_square(to).unwrap();
+                self.board.move_piece(to, from, piece_moved, side);
+            },
+            MoveFlags::Capture => {
+                let (piece_moved, _) = self.board.get_piece_at_square(to).unwrap();
+                self.board.move_piece(to, from, piece_moved, side);
+                self.board.place_piece_at_square(to, undo.captured_piece.unwrap(), opponent);
+            },
+            MoveFlags::EpCapture => {
+                let (file, _) = to.get_file_rank();
+                let (_, rank) = from.get_file_rank();
+                let captured_square = Square::new_from_file_rank(file, rank).unwrap();
+                self.board.move_piece(to, from, PieceType::Pawn, side);
+                self.board.place_piece_at_square(captured_square, PieceType::Pawn, opponent);
+            },
+            MoveFlags::KingCastle => {
+                let (king_to, rook_from, rook_to) = match side {
+                    Side::White => (WHITE_KING_KINGSIDE_END_INDEX, WHITE_ROOK_KINGSIDE_STATING_INDEX, WHITE_ROOK_KINGSIDE_END_INDEX),
+                    Side::Black => (BLACK_KING_KINGSIDE_END_INDEX, BLACK_ROOK_KINGSIDE_STATING_INDEX, BLACK_ROOK_KINGSIDE_END_INDEX),
+                };
+                self.board.move_piece(Square::new(king_to), from, PieceType::King, side);
+                self.board.move_piece(Square::new(rook_to), Square::new(rook_from), PieceType::Rook, side);
+            },
+            MoveFlags::QueenCastle => {
+                let (king_to, rook_from, rook_to) = match side {
+                    Side::White => (WHITE_KING_QUEENSIDE_END_INDEX, WHITE_ROOK_QUEENSIDE_STARTING_INDEX, WHITE_ROOK_QUEENSIDE_END_INDEX),
+                    Side::Black => (BLACK_KING_QUEENSIDE_END_INDEX, BLACK_ROOK_QUEENSIDE_STARTING_INDEX, BLACK_ROOK_QUEENSIDE_END_INDEX),
+                };
+                self.board.move_piece(Square::new(king_to), from, PieceType::King, side);
+                self.board.move_piece(Square::new(rook_to), Square::new(rook_from), PieceType::Rook, side);
+            },
+            MoveFlags::KnightPromotion | MoveFlags::BishopPromotion | MoveFlags::RookPromotion | MoveFlags::QueenPromotion => {
+                self.board.remove_piece_at_square(to, flags.get_promotion_piece().unwrap(), side);
+                self.board.place_piece_at_square(from, PieceType::Pawn, side);
+            },
+            MoveFlags::KnightPromoCapture | MoveFlags::BishopPromoCapture | MoveFlags::RookPromoCapture | MoveFlags::QueenPromoCapture => {
+                self.board.remove_piece_at_square(to, flags.get_promotion_piece().unwrap(), side);
+                self.board.place_piece_at_square(from, PieceType::Pawn, side);
+                self.board.place_piece_at_square(to, undo.captured_piece.unwrap(), opponent);
+            },
+        }
 
+        self.side_to_move = side;
+        self.castling_rights = undo.castling_rights;
+        self.en_passant = undo.en_passant;
+        self.half_move_clock = undo.half_move_clock;
+        self.full_move_count = undo.full_move_count;
+        self.zobrist_key = undo.zobrist_key;
+        self.pawn_key = undo.pawn_key;
+        self.material_key = undo.material_key;
+        self.checkers = undo.checkers;
+    }
+    // Applies the move to the board and to all of the metadata except of the keys.
+    // The move is expected to be at least pseudo legal for the side to move.
+    fn apply_move(&mut self, mov: &Move) {
+        let (flags, from, to) = mov.decode();
+        let side = self.side_to_move;
+        let opponent = side.opposite();
+        let (piece_moved, _) = self.board.get_piece_at_square(from)
+            .expect("Attempted to make a move from an empty square");
 
+        let is_capture = mov.is_capture();
+        self.en_passant = None;
 
+        match flags {
+            MoveFlags::Quiet => {
+                self.board.move_piece(from, to, piece_moved, side);
+            },
+            MoveFlags::DoublePawnPush => {
+                self.board.move_piece(from, to, piece_moved, side);
+                // Square in between is the one enemy pawns capture on
+                self.en_passant = Some(Square::new(((from.get_index() + to.get_index()) / 2) as u8));
+            },
+            MoveFlags::Capture => {
+                let (captured_piece, _) = self.board.get_piece_at_square(to)
+                    .expect("Capture move has no piece to capture");
+                self.board.remove_piece_at_square(to, captured_piece, opponent);
+                self.board.move_piece(from, to, piece_moved, side);
+            },
+            MoveFlags::EpCapture => {
+                // Captured pawn stays on the same rank as the capturing one, on the file of the destination
+                let (file, _) = to.get_file_rank();
+                let (_, rank) = from.get_file_rank();
+                let captured_square = Square::new_from_file_rank(file, rank).unwrap();
+                self.board.remove_piece_at_square(captured_square, PieceType::Pawn, opponent);
+                self.board.move_piece(from, to, piece_moved, side);
+            },
+            MoveFlags::KingCastle => {
+                let (king_to, rook_from, rook_to) = match side {
+                    Side::White => (WHITE_KING_KINGSIDE_END_INDEX, WHITE_ROOK_KINGSIDE_STATING_INDEX, WHITE_ROOK_KINGSIDE_END_INDEX),
+                    Side::Black => (BLACK_KING_KINGSIDE_END_INDEX, BLACK_ROOK_KINGSIDE_STATING_INDEX, BLACK_ROOK_KINGSIDE_END_INDEX),
+                };
+                self.board.move_piece(from, Square::new(king_to), PieceType::King, side);
+                self.board.move_piece(Square::new(rook_from), Square::new(rook_to), PieceType::Rook, side);
+            },
+            MoveFlags::QueenCastle => {
+                let (king_to, rook_from, rook_to) = match side {
+                    Side::White => (WHITE_KING_QUEENSIDE_END_INDEX, WHITE_ROOK_QUEENSIDE_STARTING_INDEX, WHITE_ROOK_QUEENSIDE_END_INDEX),
+                    Side::Black => (BLACK_KING_QUEENSIDE_END_INDEX, BLACK_ROOK_QUEENSIDE_STARTING_INDEX, BLACK_ROOK_QUEENSIDE_END_INDEX),
+                };
+                self.board.move_piece(from, Square::new(king_to), PieceType::King, side);
+                self.board.move_piece(Square::new(rook_from), Square::new(rook_to), PieceType::Rook, side);
+            },
+            MoveFlags::KnightPromotion | MoveFlags::BishopPromotion | MoveFlags::RookPromotion | MoveFlags::QueenPromotion => {
+                self.board.remove_piece_at_square(from, piece_moved, side);
+                self.board.place_piece_at_square(to, flags.get_promotion_piece().unwrap(), side);
+            },
+            MoveFlags::KnightPromoCapture | MoveFlags::BishopPromoCapture | MoveFlags::RookPromoCapture | MoveFlags::QueenPromoCapture => {
+                let (captured_piece, _) = self.board.get_piece_at_square(to)
+                    .expect("Capture move has no piece to capture");
+                self.board.remove_piece_at_square(to, captured_piece, opponent);
+                self.board.remove_piece_at_square(from, piece_moved, side);
+                self.board.place_piece_at_square(to, flags.get_promotion_piece().unwrap(), side);
+            },
+        }
 
-        todo!()
+        self.castling_rights = castling_rights_after_move(self.castling_rights, from, to);
 
+        // Clocks
+        if piece_moved == PieceType::Pawn || is_capture {
+            self.half_move_clock = 0;
+        } else {
+            self.half_move_clock = self.half_move_clock.saturating_add(1);
+        }
+        if side == Side::Black {
+            self.full_move_count = self.full_move_count.saturating_add(1);
+        }
+        self.side_to_move = opponent;
+        self.checkers = self.compute_checkers();
     }
-    fn make_move_with_zobrist(&self, mov: &Move, new_zobrist_key: u64) -> Gamestate {
-        todo!()
-    }
-    pub fn undo_move(&mut self, mov: Move) {
-        todo!()
+}
+
+// Any move that touches the king or rook starting square (from it, or onto it as a capture)
+// means castling is no longer possible on that side, so rights are dropped by looking at squares alone
+pub(crate) fn castling_rights_after_move(mut castling_rights: CastlingRights, from: Square, to: Square) -> CastlingRights {
+    for square in [from.get_index() as u8, to.get_index() as u8] {
+        match square {
+            WHITE_KING_STARTING_INDEX => castling_rights.disable_full_side(Side::White),
+            BLACK_KING_STARTING_INDEX => castling_rights.disable_full_side(Side::Black),
+            WHITE_ROOK_KINGSIDE_STATING_INDEX => castling_rights.disable_part_of_side(Side::White, CastlingSide::Kingside),
+            WHITE_ROOK_QUEENSIDE_STARTING_INDEX => castling_rights.disable_part_of_side(Side::White, CastlingSide::Queenside),
+            BLACK_ROOK_KINGSIDE_STATING_INDEX => castling_rights.disable_part_of_side(Side::Black, CastlingSide::Kingside),
+            BLACK_ROOK_QUEENSIDE_STARTING_INDEX => castling_rights.disable_part_of_side(Side::Black, CastlingSide::Queenside),
+            _ => (),
+        }
     }
-    
+    castling_rights
 }
 
 
 // Structure of the moves is 4 flag bits, 6 bits for the index of square to move, and 6 bits for index of square to move to
 // ****  ******  ****** - Total of 16 bits 
 // flags toIndex fromIndex
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Move(u16);
 impl Move {
+    // a1 to a1 can never be a real move, so it is used as an empty placeholder
+    pub const NULL: Move = Move(0);
+
     #[inline(always)]
     pub fn encode(from: Square, to: Square, flags: MoveFlags) -> Move {
         Self(
@@ -90,6 +312,16 @@ impl Move {
             | (flags as u16) << MOVE_FLAGS_OFFSET
         )
     }
+    // Moves stored outside of the engine (transposition table, opening book) are kept as raw bits.
+    // Raw move can have an invalid flag, so it has to be checked with try_get_flags before it is used
+    #[inline(always)]
+    pub const fn from_u16(raw: u16) -> Move {
+        Self(raw)
+    }
+    #[inline(always)]
+    pub const fn to_u16(self) -> u16 {
+        self.0
+    }
     #[inline(always)]
     pub fn decode(&self) -> (MoveFlags, Square, Square) { // Flags, Square
         (self.get_flags(), self.get_from_square(), self.get_to_square())
@@ -107,6 +339,10 @@ impl Move {
     pub fn get_flags(&self) -> MoveFlags {
         MoveFlags::from_u8(((self.0 >> MOVE_FLAGS_OFFSET) & FLAGS_MASK) as u8)
     }
+    #[inline(always)]
+    pub fn try_get_flags(&self) -> Option<MoveFlags> {
+        MoveFlags::try_from_u8(((self.0 >> MOVE_FLAGS_OFFSET) & FLAGS_MASK) as u8)
+    }
 
     #[inline(always)]
     pub fn is_capture(&self) -> bool {
@@ -140,7 +376,7 @@ impl Move {
 }
 
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MoveFlags {
     // QUIET
     Quiet =              0b0000, // 0
@@ -168,26 +404,33 @@ pub enum MoveFlags {
 }
 impl MoveFlags {
     pub fn from_u8(value: u8) -> Self {
+        match Self::try_from_u8(value) {
+            Some(flags) => flags,
+            None => unreachable!("Invalid move flag: {:#b}", value),
+        }
+    }
+    // 0b0110 and 0b0111 are not used by the encoding, same as anything above 4 bits
+    pub fn try_from_u8(value: u8) -> Option<Self> {
         match value {
-            0b0000 => MoveFlags::Quiet,
+            0b0000 => Some(MoveFlags::Quiet),
 
-            0b0001 => MoveFlags::DoublePawnPush,
+            0b0001 => Some(MoveFlags::DoublePawnPush),
 
-            0b0010 => MoveFlags::KingCastle,
-            0b0011 => MoveFlags::QueenCastle,
+            0b0010 => Some(MoveFlags::KingCastle),
+            0b0011 => Some(MoveFlags::QueenCastle),
 
-            0b0100 => MoveFlags::Capture,
-            0b0101 => MoveFlags::EpCapture,
+            0b0100 => Some(MoveFlags::Capture),
+            0b0101 => Some(MoveFlags::EpCapture),
 
-            0b1000 => MoveFlags::KnightPromotion,
-            0b1001 => MoveFlags::BishopPromotion,
-            0b1010 => MoveFlags::RookPromotion,
-            0b1011 => MoveFlags::QueenPromotion,
-            0b1100 => MoveFlags::KnightPromoCapture,
-            0b1101 => MoveFlags::BishopPromoCapture,
-            0b1110 => MoveFlags::RookPromoCapture,
-            0b1111 => MoveFlags::QueenPromoCapture,
-            _ => unreachable!("Invalid move flag: {:#b}", value),
+            0b1000 => Some(MoveFlags::KnightPromotion),
+            0b1001 => Some(MoveFlags::BishopPromotion),
+            0b1010 => Some(MoveFlags::RookPromotion),
+            0b1011 => Some(MoveFlags::QueenPromotion),
+            0b1100 => Some(MoveFlags::KnightPromoCapture),
+            0b1101 => Some(MoveFlags::BishopPromoCapture),
+            0b1110 => Some(MoveFlags::RookPromoCapture),
+            0b1111 => Some(MoveFlags::QueenPromoCapture),
+            _ => None,
         }
     }
     pub fn get_promotion_piece(&self) -> Option<PieceType> {
@@ -0,0 +1,94 @@
+use crate::gamestate::{
+    board::{Bitboard, BitboardIter, PieceType, Side},
+    defs::*,
+    Gamestate,
+};
+
+// Polyglot is the hashing used by opening books and most other engines, check http://hgm.nubati.net/book_format.html
+// for the format. Keys are a fixed table of 781 random values (the Random64 array from the Polyglot sources) laid out as:
+// 768 piece-square values, 4 castling values, 8 en passant files and the turn value
+pub const POLYGLOT_KEYS_NUM: usize = 781;
+
+const CASTLING_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+
+pub struct PolyglotKeys {
+    random64: [u64; POLYGLOT_KEYS_NUM],
+}
+impl PolyglotKeys {
+    // The table has to be the Random64 array in the same order as in the Polyglot sources, otherwise keys will not match books
+    pub fn new(random64: [u64; POLYGLOT_KEYS_NUM]) -> Self {
+        PolyglotKeys { random64 }
+    }
+    pub fn hash(&self, game: &Gamestate) -> u64 {
+        let mut key: u64 = 0;
+
+        for side in [Side::White, Side::Black] {
+            for (piece_index, &bitboard) in game.board.get_pieces(side).iter().enumerate() {
+                let kind = polyglot_piece_kind(PieceType::from_u8(piece_index as u8), side);
+                for square in BitboardIter::new(bitboard) {
+                    key ^= self.random64[BOARD_NUM_OF_SQUARES * kind + square.get_index()];
+                }
+            }
+        }
+
+        let white_castling = game.castling_rights.get_for_side(Side::White);
+        let black_castling = game.castling_rights.get_for_side(Side::Black);
+        let castling = [
+            white_castling.has_kingside(),
+            white_castling.has_queenside(),
+            black_castling.has_kingside(),
+            black_castling.has_queenside(),
+        ];
+        for (i, _) in castling.iter().enumerate().filter(|(_, &allowed)| allowed) {
+            key ^= self.random64[CASTLING_OFFSET + i];
+        }
+
+        if let Some(file) = capturable_en_passant_file(game) {
+            key ^= self.random64[EN_PASSANT_OFFSET + file];
+        }
+
+        if game.side_to_move == Side::White {
+            key ^= self.random64[TURN_OFFSET];
+        }
+        key
+    }
+}
+
+// Polyglot orders pieces as black pawn, white pawn, black knight, white knight and so on up to the white king
+#[inline(always)]
+fn polyglot_piece_kind(piece_type: PieceType, side: Side) -> usize {
+    let side_index = match side {
+        Side::Black => 0,
+        Side::White => 1,
+    };
+    piece_type as usize * SIDE_NUM + side_index
+}
+
+// Unlike our own keys, Polyglot hashes the en passant file only when a pawn of the side to move stands next to the pawn
+// that was just pushed, so it could capture it. Pins are not taken into account
+fn capturable_en_passant_file(game: &Gamestate) -> Option<usize> {
+    let (file, _) = game.en_passant?.get_file_rank();
+    let file = file as usize;
+    // Rank on which the pawns able to capture en passant stand
+    let rank = match game.side_to_move {
+        Side::White => 4,
+        Side::Black => 3,
+    };
+    let pawns = game.board.get_pieces(game.side_to_move)[PieceType::Pawn as usize];
+
+    let mut neighbours: Bitboard = 0;
+    if file > 0 {
+        neighbours |= 1 << (rank * BOARD_SIDE_LENGTH + file - 1);
+    }
+    if file < BOARD_SIDE_LENGTH - 1 {
+        neighbours |= 1 << (rank * BOARD_SIDE_LENGTH + file + 1);
+    }
+    if pawns & neighbours != 0 {
+        Some(file)
+    } else {
+        None
+    }
+}
+
@@ -0,0 +1,103 @@
+use crate::gamestate::{
+    board::{Bitboard, PieceType, Side},
+    history::History,
+    Gamestate,
+};
+use crate::movegen::MoveGen;
+
+// Check https://www.chessprogramming.org/Draw for details on the draw rules
+const FIFTY_MOVE_RULE_PLIES: u8 = 100;
+const SEVENTY_FIVE_MOVE_RULE_PLIES: u8 = 150;
+const THREEFOLD_REPETITION: usize = 3;
+const FIVEFOLD_REPETITION: usize = 5;
+
+// Squares b1, d1, ..., a2, c2, ... h8 (a1 is a dark square)
+const LIGHT_SQUARES: Bitboard = 0x55AA_55AA_55AA_55AA;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    // Side that gave the mate
+    Checkmate(Side),
+    Stalemate,
+    // Under FIDE rules the fifty-move rule and threefold repetition end the game only when a player claims the draw,
+    // while the seventy-five-move rule and fivefold repetition end it automatically
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    InsufficientMaterial,
+}
+impl GameStatus {
+    pub fn is_game_over(&self) -> bool {
+        *self != GameStatus::Ongoing
+    }
+    // Draws that need a claim, applications adjudicating engine games usually end the game on them anyway
+    pub fn is_claimable_draw(&self) -> bool {
+        matches!(self, GameStatus::FiftyMoveRule | GameStatus::ThreefoldRepetition)
+    }
+    pub fn result(&self) -> Option<GameResult> {
+        match self {
+            GameStatus::Ongoing => None,
+            GameStatus::Checkmate(Side::White) => Some(GameResult::WhiteWins),
+            GameStatus::Checkmate(Side::Black) => Some(GameResult::BlackWins),
+            _ => Some(GameResult::Draw),
+        }
+    }
+}
+
+impl Gamestate {
+    // History has to end with the current position, as it does when positions are pushed after every move.
+    // Checkmate comes first, since a mate given with the last move allowed by the move rules still wins the game
+    pub fn status(&self, history: &History) -> GameStatus {
+        if MoveGen::new(self).gererate().is_empty() {
+            return if self.is_in_check() {
+                GameStatus::Checkmate(self.side_to_move.opposite())
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+        if self.half_move_clock >= SEVENTY_FIVE_MOVE_RULE_PLIES {
+            return GameStatus::SeventyFiveMoveRule;
+        }
+        if history.is_repetition(FIVEFOLD_REPETITION) {
+            return GameStatus::FivefoldRepetition;
+        }
+        if self.has_insufficient_material() {
+            return GameStatus::InsufficientMaterial;
+        }
+        if self.half_move_clock >= FIFTY_MOVE_RULE_PLIES {
+            return GameStatus::FiftyMoveRule;
+        }
+        if history.is_repetition(THREEFOLD_REPETITION) {
+            return GameStatus::ThreefoldRepetition;
+        }
+        GameStatus::Ongoing
+    }
+    // Positions where no sequence of legal moves can lead to a mate: lone kings, a single minor piece against a lone king,
+    // or any number of bishops standing on squares of the same colour
+    pub fn has_insufficient_material(&self) -> bool {
+        let white = self.board.get_pieces(Side::White);
+        let black = self.board.get_pieces(Side::Black);
+        let pieces = |piece_type: PieceType| white[piece_type as usize] | black[piece_type as usize];
+
+        if pieces(PieceType::Pawn) | pieces(PieceType::Rook) | pieces(PieceType::Queen) != 0 {
+            return false;
+        }
+        let knights = pieces(PieceType::Knight);
+        let bishops = pieces(PieceType::Bishop);
+
+        match (knights.count_ones(), bishops.count_ones()) {
+            (0, 0) | (1, 0) | (0, 1) => true,
+            (0, _) => bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use crate::gamestate::{
+    board::{Bitboard, PieceType, Square},
+    castling_rights::CastlingRights,
+};
+
+// Irreversible part of the position, everything else can be restored from the move itself.
+// Check https://www.chessprogramming.org/Unmake_Move for details
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UndoRecord {
+    pub captured_piece: Option<PieceType>,
+    pub castling_rights: CastlingRights,
+    pub en_passant: Option<Square>,
+    pub half_move_clock: u8,
+    pub full_move_count: u8,
+    pub zobrist_key: u64,
+    pub pawn_key: u64,
+    pub material_key: u64,
+    pub checkers: Bitboard,
+}
+
+pub struct UndoStack {
+    list: Vec<UndoRecord>
+}
+impl Default for UndoStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl UndoStack {
+    pub fn new() -> Self {
+        Self {
+            list: vec![]
+        }
+    }
+    pub fn push(&mut self, record: UndoRecord) {
+        self.list.push(record);
+    }
+    pub fn pop(&mut self) -> Option<UndoRecord> {
+        self.list.pop()
+    }
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+}
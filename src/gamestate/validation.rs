@@ -0,0 +1,111 @@
+use crate::gamestate::{
+    board::{BitboardIter, PieceType, Side, Square},
+    castling_rights::CastlingSide,
+    zobrist::*,
+    Gamestate,
+};
+use crate::movegen::defs::{FIRST_RANK, EIGHTH_RANK};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionError {
+    #[error("{0:?} has {1} kings, expected exactly 1")]
+    InvalidKingCount(Side, u32),
+    #[error("Pawn on the first or eighth rank at {}", .0.to_algebraic_notation())]
+    PawnOnBackRank(Square),
+    #[error("{0:?} has {1:?} castling rights, but the king or the rook is not on its home square")]
+    InvalidCastlingRights(Side, CastlingSide),
+    #[error("En passant is set, but no pawn could have just made a double push there")]
+    InvalidEnPassant,
+    #[error("{0:?} is in check, but it is not its turn to move")]
+    OpponentInCheck(Side),
+    #[error("More than one piece stands on {}", .0.to_algebraic_notation())]
+    OverlappingPieces(Square),
+}
+
+impl Gamestate {
+    // Checks that the position could come up in a game, move generation and make_move rely on all of this
+    pub fn validate(&self) -> Result<(), PositionError> {
+        // Board does not clear a square before placing a piece on it, so piece bitboards can overlap
+        let mut occupancy = 0;
+        for side in [Side::White, Side::Black] {
+            for &bitboard in self.board.get_pieces(side).iter() {
+                if let Some(square) = BitboardIter::new(occupancy & bitboard).next() {
+                    return Err(PositionError::OverlappingPieces(square));
+                }
+                occupancy |= bitboard;
+            }
+        }
+
+        for side in [Side::White, Side::Black] {
+            let kings = self.board.get_pieces(side)[PieceType::King as usize].count_ones();
+            if kings != 1 {
+                return Err(PositionError::InvalidKingCount(side, kings));
+            }
+        }
+
+        let pawns = self.board.get_pieces(Side::White)[PieceType::Pawn as usize] | self.board.get_pieces(Side::Black)[PieceType::Pawn as usize];
+        if let Some(square) = BitboardIter::new(pawns & (FIRST_RANK | EIGHTH_RANK)).next() {
+            return Err(PositionError::PawnOnBackRank(square));
+        }
+
+        self.validate_castling_rights()?;
+        self.validate_en_passant()?;
+
+        let opponent = self.side_to_move.opposite();
+        let opponent_king = self.board.get_king_square(opponent).unwrap();
+        if self.is_square_attacked(opponent_king, self.side_to_move) {
+            return Err(PositionError::OpponentInCheck(opponent));
+        }
+        Ok(())
+    }
+
+    fn validate_castling_rights(&self) -> Result<(), PositionError> {
+        let home_squares = [
+            (Side::White, WHITE_KING_STARTING_INDEX, WHITE_ROOK_KINGSIDE_STATING_INDEX, WHITE_ROOK_QUEENSIDE_STARTING_INDEX),
+            (Side::Black, BLACK_KING_STARTING_INDEX, BLACK_ROOK_KINGSIDE_STATING_INDEX, BLACK_ROOK_QUEENSIDE_STARTING_INDEX),
+        ];
+        for (side, king, kingside_rook, queenside_rook) in home_squares {
+            let has_piece = |piece_type: PieceType, index: u8| {
+                self.board.get_pieces(side)[piece_type as usize] & Square::new(index).get_mask() != 0
+            };
+            let rights = self.castling_rights.get_for_side(side);
+            let king_home = has_piece(PieceType::King, king);
+
+            if rights.has_kingside() && !(king_home && has_piece(PieceType::Rook, kingside_rook)) {
+                return Err(PositionError::InvalidCastlingRights(side, CastlingSide::Kingside));
+            }
+            if rights.has_queenside() && !(king_home && has_piece(PieceType::Rook, queenside_rook)) {
+                return Err(PositionError::InvalidCastlingRights(side, CastlingSide::Queenside));
+            }
+        }
+        Ok(())
+    }
+
+    // Pawn that just made a double push has to stand in front of the en passant square,
+    // with both the en passant square and the square it came from being empty
+    fn validate_en_passant(&self) -> Result<(), PositionError> {
+        let Some(en_passant) = self.en_passant else {
+            return Ok(());
+        };
+        let (file, rank) = en_passant.get_file_rank();
+        let pushed_side = self.side_to_move.opposite();
+        // Ranks the pawn started from, passed thru and landed on
+        let (start_rank, passed_rank, pushed_rank) = match pushed_side {
+            Side::White => (1, 2, 3),
+            Side::Black => (6, 5, 4),
+        };
+        if rank != passed_rank {
+            return Err(PositionError::InvalidEnPassant);
+        }
+        let occupancy = self.board.get_combined_occupancy();
+        let square = |rank: u8| Square::new_from_file_rank(file, rank).unwrap().get_mask();
+
+        let pawn_pushed = self.board.get_pieces(pushed_side)[PieceType::Pawn as usize] & square(pushed_rank) != 0;
+        let path_empty = occupancy & (square(start_rank) | square(passed_rank)) == 0;
+        if !(pawn_pushed && path_empty) {
+            return Err(PositionError::InvalidEnPassant);
+        }
+        Ok(())
+    }
+}
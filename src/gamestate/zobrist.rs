@@ -2,66 +2,65 @@ use crate::gamestate::*;
 use crate::gamestate::defs::*;
 use crate::gamestate::board::*;
 
-use castling_rights::CastlingSide;
 use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
-use rayon::prelude::*;
 use lazy_static::lazy_static;
 
 const SEED: u64 = 1231231;
+const MATERIAL_SEED: u64 = 3213213;
 lazy_static! {
     // The map looks like this - side of the piece - square on which piece is located - type of the piece, resulting in 768 random values for each possible combination
     static ref PIECE_HASHES: [[[u64; PIECE_TYPES_NUM]; BOARD_NUM_OF_SQUARES]; SIDE_NUM] = generate_pieces_hashes(SEED);
     static ref SIDE_HASHES: [u64; SIDE_NUM] = generate_side_hashes(SEED);
     static ref CASTLING_HASHES: [u64; CASTLING_CONFIGURATIONS_NUM] = generate_castling_hashes(SEED);
     static ref EN_PASSANT_HASHES: [u64; BOARD_SIDE_LENGTH] = generate_enpassant_hashes(SEED);
+    // Side of the piece - type of the piece - how many pieces of this type were there before, the n-th piece of a type
+    // adds its own value, so the key depends only on piece counts and not on squares
+    static ref MATERIAL_HASHES: [[[u64; MAX_PIECES_OF_TYPE]; PIECE_TYPES_NUM]; SIDE_NUM] = generate_material_hashes(MATERIAL_SEED);
 }
 
 
 const WHITE_SIDE: usize = 0;
 const BLACK_SIDE: usize = 1;
 // Castling initial positions
-const WHITE_KING_STARTING_INDEX: u8 = 4;
-const BLACK_KING_STARTING_INDEX: u8 = 60;
+pub const WHITE_KING_STARTING_INDEX: u8 = 4;
+pub const BLACK_KING_STARTING_INDEX: u8 = 60;
 // -- kingside
-const WHITE_ROOK_KINGSIDE_STATING_INDEX: u8 = 7;
-const BLACK_ROOK_KINGSIDE_STATING_INDEX: u8 = 63;
+pub const WHITE_ROOK_KINGSIDE_STATING_INDEX: u8 = 7;
+pub const BLACK_ROOK_KINGSIDE_STATING_INDEX: u8 = 63;
 // --queenside
-const WHITE_ROOK_QUEENSIDE_STARTING_INDEX: u8 = 0;
-const BLACK_ROOK_QUEENSIDE_STARTING_INDEX: u8 = 56;
+pub const WHITE_ROOK_QUEENSIDE_STARTING_INDEX: u8 = 0;
+pub const BLACK_ROOK_QUEENSIDE_STARTING_INDEX: u8 = 56;
 
 // Castling end positions
 // -- kings kingside
-const WHITE_KING_KINGSIDE_END_INDEX: u8 = 6;
-const BLACK_KING_KINGSIDE_END_INDEX: u8 = 62; 
+pub const WHITE_KING_KINGSIDE_END_INDEX: u8 = 6;
+pub const BLACK_KING_KINGSIDE_END_INDEX: u8 = 62; 
 // -- kings queenside
-const WHITE_KING_QUEENSIDE_END_INDEX: u8 = 2;
-const BLACK_KING_QUEENSIDE_END_INDEX: u8 = 58; 
+pub const WHITE_KING_QUEENSIDE_END_INDEX: u8 = 2;
+pub const BLACK_KING_QUEENSIDE_END_INDEX: u8 = 58; 
 // -- rooks kingside 
-const WHITE_ROOK_KINGSIDE_END_INDEX: u8 = 5;
-const BLACK_ROOK_KINGSIDE_END_INDEX: u8 = 61;
+pub const WHITE_ROOK_KINGSIDE_END_INDEX: u8 = 5;
+pub const BLACK_ROOK_KINGSIDE_END_INDEX: u8 = 61;
 // -- rooks queenside 
-const WHITE_ROOK_QUEENSIDE_END_INDEX: u8 = 3;
-const BLACK_ROOK_QUEENSIDE_END_INDEX: u8 = 59;
+pub const WHITE_ROOK_QUEENSIDE_END_INDEX: u8 = 3;
+pub const BLACK_ROOK_QUEENSIDE_END_INDEX: u8 = 59;
 
 pub struct Zobrist;
 impl Zobrist {
-    // Note that this function generetes always a new hash, incremental hash updates will be added soon
+    // Computes the key from scratch, moves update it with incremental_hash_update instead
     pub fn hash(game: &Gamestate) -> u64 {
         let mut zobrist_key:u64 = 0;
         // Pieces
-        let piece_hashes: u64 = (0..BOARD_NUM_OF_SQUARES)
-            .into_par_iter()
-            .filter_map(|i| {
-                // Get the piece at the given square
-                let square = Square::new(i as u8);
-                let piece = game.board.get_piece_at_square(square);
-                // Map the piece to its corresponding Zobrist hash
-                piece.map(|(piece_type, side)| 
-                    PIECE_HASHES[side as usize][square.get_index()][piece_type as usize]
-                )
-            })
-            .reduce(|| 0, |acc, x| acc ^ x);
+        let mut piece_hashes: u64 = 0;
+        for (side, pieces) in [(Side::White, &game.board.white_pieces), (Side::Black, &game.board.black_pieces)] {
+            for (piece_type, &bitboard) in pieces.iter().enumerate() {
+                // Map every piece to its corresponding Zobrist hash
+                for square in BitboardIter::new(bitboard) {
+                    piece_hashes ^= PIECE_HASHES[side as usize][square.get_index()][piece_type];
+                }
+            }
+        }
 
         zobrist_key ^= piece_hashes;
         // Side to move
@@ -76,52 +75,86 @@ impl Zobrist {
         // Castling rights
         zobrist_key ^= CASTLING_HASHES[game.castling_rights.get() as usize];
         // En passant
-        for i in 0..BOARD_SIDE_LENGTH {
-            if ((1 << i) & game.en_passant as usize) != 0 {
-                zobrist_key ^= EN_PASSANT_HASHES[i];
-            }
+        if let Some(square) = game.en_passant {
+            let (file, _) = square.get_file_rank();
+            zobrist_key ^= EN_PASSANT_HASHES[file as usize];
         }
         zobrist_key // Return the Zobrist hash key
     }
+    // Key of the pawn structure used by evaluation caches, kings are included since pawn evaluation depends on them
+    pub fn pawn_hash(game: &Gamestate) -> u64 {
+        let mut pawn_key: u64 = 0;
+        for side in [Side::White, Side::Black] {
+            for piece_type in [PieceType::Pawn, PieceType::King] {
+                for square in BitboardIter::new(game.board.get_pieces(side)[piece_type as usize]) {
+                    pawn_key ^= PIECE_HASHES[side as usize][square.get_index()][piece_type as usize];
+                }
+            }
+        }
+        pawn_key
+    }
+    // Material signature, equal for all positions with the same piece counts
+    pub fn material_hash(game: &Gamestate) -> u64 {
+        let mut material_key: u64 = 0;
+        for side in [Side::White, Side::Black] {
+            for (piece_type, &bitboard) in game.board.get_pieces(side).iter().enumerate() {
+                for count in 0..bitboard.count_ones() as usize {
+                    material_key ^= MATERIAL_HASHES[side as usize][piece_type][count];
+                }
+            }
+        }
+        material_key
+    }
 
     // Check this for more info https://www.chessprogramming.org/Incremental_Updates
-    // This function should be used BEFORE applying the move to the gamestate
+    // This function should be used BEFORE applying the move to the gamestate, the result is always equal to
+    // the one of the hash function called after the move is applied
     pub fn incremental_hash_update(game: &Gamestate, mov: &Move) -> u64 {
+        let (flags, square_from, square_to) = mov.decode();
         let side_to_move = game.side_to_move;
-        let square_from = mov.get_from_square();
-        let square_to = mov.get_to_square();
-        let piece_moved = game.board.get_piece_at_square(square_from).unwrap().0;
-        
+        let piece_moved = game.board.get_piece_at_square(square_from)
+            .expect("Attempted to hash a move from an empty square").0;
+
         let mut new_zobrist_key = game.zobrist_key;
         update_side_hash(&mut new_zobrist_key, side_to_move);
-        match mov.get_flags() {
-            MoveFlags::Quiet | MoveFlags::DoublePawnPush => {
+        // En passant is possible only right after the double push, so the old one is cleared for any move
+        update_en_passant_hash(&mut new_zobrist_key, game.en_passant);
+
+        match flags {
+            MoveFlags::Quiet => {
                 update_piece_moved_hash(&mut new_zobrist_key, piece_moved, square_from, square_to, side_to_move);
             },
-            MoveFlags::Capture => {
+            MoveFlags::DoublePawnPush => {
                 update_piece_moved_hash(&mut new_zobrist_key, piece_moved, square_from, square_to, side_to_move);
-                
+                let (file, _) = square_to.get_file_rank();
+                new_zobrist_key ^= EN_PASSANT_HASHES[file as usize];
+            },
+            MoveFlags::Capture => {
                 let captured_piece: PieceType = game.board.get_piece_at_square(square_to).unwrap().0;
                 update_captured_piece_hash(&mut new_zobrist_key, side_to_move, square_to, captured_piece);
+                update_piece_moved_hash(&mut new_zobrist_key, piece_moved, square_from, square_to, side_to_move);
             },
             MoveFlags::EpCapture => {
-                todo!()
-            }
+                // Captured pawn stays on the same rank as the capturing one, on the file of the destination
+                let (file, _) = square_to.get_file_rank();
+                let (_, rank) = square_from.get_file_rank();
+                let captured_square = Square::new_from_file_rank(file, rank).unwrap();
+                update_captured_piece_hash(&mut new_zobrist_key, side_to_move, captured_square, PieceType::Pawn);
+                update_piece_moved_hash(&mut new_zobrist_key, piece_moved, square_from, square_to, side_to_move);
+            },
             // Catling moves
             MoveFlags::KingCastle => {
                 update_kingside_castling_pieces_hash(&mut new_zobrist_key, side_to_move);
-                update_castling_rights_disable_full_side_hash(&mut new_zobrist_key, side_to_move, game.castling_rights.clone());
             },
             MoveFlags::QueenCastle => {
                 update_queenside_castling_pieces_hash(&mut new_zobrist_key, side_to_move);
-                update_castling_rights_disable_full_side_hash(&mut new_zobrist_key, side_to_move, game.castling_rights.clone());
             },
             // Quiet promotions
             MoveFlags::QueenPromotion | MoveFlags::KnightPromotion | MoveFlags::BishopPromotion | MoveFlags::RookPromotion => {
                 // Clear old piece position hash 
                 new_zobrist_key ^= PIECE_HASHES[side_to_move as usize][square_from.get_index()][piece_moved as usize];
                 // Set promoted piece hash
-                new_zobrist_key ^= PIECE_HASHES[side_to_move as usize][square_to.get_index()][mov.get_flags().get_promotion_piece().unwrap() as usize];
+                new_zobrist_key ^= PIECE_HASHES[side_to_move as usize][square_to.get_index()][flags.get_promotion_piece().unwrap() as usize];
             },
             // Capture promotions 
             MoveFlags::QueenPromoCapture | MoveFlags::KnightPromoCapture | MoveFlags::BishopPromoCapture | MoveFlags::RookPromoCapture => {
@@ -131,11 +164,74 @@ impl Zobrist {
                 let captured_piece = game.board.get_piece_at_square(square_to).unwrap().0;
                 update_captured_piece_hash(&mut new_zobrist_key, side_to_move, square_to, captured_piece);
                 // Set promoted piece hash
-                new_zobrist_key ^= PIECE_HASHES[side_to_move as usize][square_to.get_index()][mov.get_flags().get_promotion_piece().unwrap() as usize];
+                new_zobrist_key ^= PIECE_HASHES[side_to_move as usize][square_to.get_index()][flags.get_promotion_piece().unwrap() as usize];
             },
         }
 
-        return new_zobrist_key
+        // Rights change the same way for every move type (castling included, since the king leaves its square),
+        // including the quiet king and rook moves and the captures of a rook on its starting square
+        let new_castling_rights = castling_rights_after_move(game.castling_rights, square_from, square_to);
+        update_castling_rights_hash(&mut new_zobrist_key, game.castling_rights, new_castling_rights);
+
+        new_zobrist_key
+    }
+    // Same as incremental_hash_update, should be used BEFORE applying the move
+    pub fn incremental_pawn_hash_update(game: &Gamestate, mov: &Move) -> u64 {
+        let (_, square_from, square_to) = mov.decode();
+        let side_to_move = game.side_to_move;
+        let piece_moved = game.board.get_piece_at_square(square_from)
+            .expect("Attempted to hash a move from an empty square").0;
+
+        let mut new_pawn_key = game.pawn_key;
+        if let Some((PieceType::Pawn, captured_square)) = get_captured_piece(game, mov) {
+            new_pawn_key ^= PIECE_HASHES[side_to_move.opposite() as usize][captured_square.get_index()][PieceType::Pawn as usize];
+        }
+        match piece_moved {
+            // Promoted pawn leaves the pawn structure
+            PieceType::Pawn if mov.is_promotion() => {
+                new_pawn_key ^= PIECE_HASHES[side_to_move as usize][square_from.get_index()][PieceType::Pawn as usize];
+            },
+            // For castling only the king matters, and its squares are the ones the move is encoded with
+            PieceType::Pawn | PieceType::King => {
+                update_piece_moved_hash(&mut new_pawn_key, piece_moved, square_from, square_to, side_to_move);
+            },
+            _ => (),
+        }
+        new_pawn_key
+    }
+    // Same as incremental_hash_update, should be used BEFORE applying the move. Only captures and promotions change material
+    pub fn incremental_material_hash_update(game: &Gamestate, mov: &Move) -> u64 {
+        let flags = mov.get_flags();
+        let side_to_move = game.side_to_move;
+        let opponent = side_to_move.opposite();
+
+        let mut new_material_key = game.material_key;
+        if let Some((captured_piece, _)) = get_captured_piece(game, mov) {
+            // The last piece of the type is the one removed
+            let count = game.board.get_pieces(opponent)[captured_piece as usize].count_ones() as usize;
+            new_material_key ^= MATERIAL_HASHES[opponent as usize][captured_piece as usize][count - 1];
+        }
+        if let Some(promotion_piece) = flags.get_promotion_piece() {
+            let pawns = game.board.get_pieces(side_to_move)[PieceType::Pawn as usize].count_ones() as usize;
+            new_material_key ^= MATERIAL_HASHES[side_to_move as usize][PieceType::Pawn as usize][pawns - 1];
+            let count = game.board.get_pieces(side_to_move)[promotion_piece as usize].count_ones() as usize;
+            new_material_key ^= MATERIAL_HASHES[side_to_move as usize][promotion_piece as usize][count];
+        }
+        new_material_key
+    }
+}
+
+// Piece captured by the move and the square it is captured on, which differs from the destination for en passant
+fn get_captured_piece(game: &Gamestate, mov: &Move) -> Option<(PieceType, Square)> {
+    let (flags, square_from, square_to) = mov.decode();
+    match flags {
+        MoveFlags::EpCapture => {
+            let (file, _) = square_to.get_file_rank();
+            let (_, rank) = square_from.get_file_rank();
+            Some((PieceType::Pawn, Square::new_from_file_rank(file, rank).unwrap()))
+        },
+        _ if mov.is_capture() => game.board.get_piece_at_square(square_to).map(|(piece_type, _)| (piece_type, square_to)),
+        _ => None,
     }
 }
 
@@ -162,6 +258,13 @@ fn update_side_hash(zobrist_key: &mut u64, side_to_move: Side) {
         },
     }
 }
+// Only the file of the en passant square is hashed
+fn update_en_passant_hash(zobrist_key: &mut u64, en_passant: Option<Square>) {
+    if let Some(square) = en_passant {
+        let (file, _) = square.get_file_rank();
+        *zobrist_key ^= EN_PASSANT_HASHES[file as usize];
+    }
+}
 fn update_captured_piece_hash(zobrist_key: &mut u64, side_to_move: Side, positon: Square, captured_piece: PieceType) {
     match side_to_move {
         Side::White => {
@@ -196,7 +299,7 @@ fn update_kingside_castling_pieces_hash(zobrist_key: &mut u64, side_to_move: Sid
             update_piece_moved_hash(zobrist_key, PieceType::Rook, 
                 Square::new(BLACK_ROOK_KINGSIDE_STATING_INDEX), 
                 Square::new(BLACK_ROOK_KINGSIDE_END_INDEX),
-                Side::White);
+                Side::Black);
         }
     }
 }
@@ -224,28 +327,16 @@ fn update_queenside_castling_pieces_hash(zobrist_key: &mut u64, side_to_move: Si
             update_piece_moved_hash(zobrist_key, PieceType::Rook, 
                 Square::new(BLACK_ROOK_QUEENSIDE_STARTING_INDEX), 
                 Square::new(BLACK_ROOK_QUEENSIDE_END_INDEX),
-                Side::White);
+                Side::Black);
         }
     }
 }
-fn update_castling_rights_disable_full_side_hash(zobrist_key: &mut u64, side: Side, castling_rights: CastlingRights) {
+fn update_castling_rights_hash(zobrist_key: &mut u64, old_rights: CastlingRights, new_rights: CastlingRights) {
     // Clear old castling
-    *zobrist_key ^= CASTLING_HASHES[castling_rights.get() as usize];
-    // Get new rights
-    let mut new_rights = castling_rights;
-    new_rights.disable_full_side(side);
+    *zobrist_key ^= CASTLING_HASHES[old_rights.get() as usize];
     // Set new rights
     *zobrist_key ^= CASTLING_HASHES[new_rights.get() as usize];
 }
-fn update_castling_rights_disable_part_of_side_for_side_to_move_hash(zobrist_key: &mut u64, side: Side, castling_rights: CastlingRights, side_to_disable: CastlingSide) {
-  // Clear old castling
-  *zobrist_key ^= CASTLING_HASHES[castling_rights.get() as usize];
-  // Get new rights
-  let mut new_rights = castling_rights;
-  new_rights.disable_part_of_side(side, side_to_disable);
-  // Hash new rights
-  *zobrist_key ^= CASTLING_HASHES[new_rights.get() as usize];
-}
 
 // This functiond are used to generete static arrays of random values
 fn generate_pieces_hashes(seed: u64) -> [[[u64; PIECE_TYPES_NUM]; BOARD_NUM_OF_SQUARES]; SIDE_NUM] {
@@ -304,3 +395,16 @@ fn generate_enpassant_hashes(seed: u64) -> [u64; BOARD_SIDE_LENGTH] {
     }
     array
 }
+fn generate_material_hashes(seed: u64) -> [[[u64; MAX_PIECES_OF_TYPE]; PIECE_TYPES_NUM]; SIDE_NUM] {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut array = [[[0; MAX_PIECES_OF_TYPE]; PIECE_TYPES_NUM]; SIDE_NUM];
+
+    for side in array.iter_mut() {
+        for piece_type in side.iter_mut() {
+            for hash in piece_type.iter_mut() {
+                *hash = rng.gen();
+            }
+        }
+    }
+    array
+}
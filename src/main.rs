@@ -29,8 +29,8 @@ fn print_bitboard(bitboard: u64) {
 }
 
 fn main() {
-    let mut game: Gamestate = Fen(DEFAULT_FEN.to_string()).process().unwrap();
-    let moves = MoveGen::new(&mut game).gererate();
+    let game: Gamestate = Fen(DEFAULT_FEN.to_string()).process().unwrap();
+    let moves = MoveGen::new(&game).gererate();
     print!("The move counter for knights is {}", moves.len());
     println!();
     for mov in &moves {
@@ -42,7 +42,7 @@ fn main() {
         println!();
     }
     print!("Game hash: {}", game.zobrist_key);
-    print!("{}", MoveDisplayWrapper(moves));
+    print!("{}", MoveDisplayWrapper(moves.to_vec()));
 
 
 }
@@ -0,0 +1,78 @@
+use crate::gamestate::board::*;
+use super::magic_bitboards;
+#[cfg(target_arch = "x86_64")]
+use super::pext_bitboards;
+
+use lazy_static::lazy_static;
+
+// Single entry point for sliding pieces attacks, the rest of the engine should not care which backend is used.
+// PEXT is picked at runtime if CPU supports BMI2, magic bitboards are used otherwise.
+// `force-magic` and `force-pext` cargo features skip the detection.
+lazy_static! {
+    static ref SLIDER_BACKEND: SliderBackend = detect_slider_backend();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliderBackend {
+    Magic,
+    Pext,
+}
+
+pub fn slider_backend() -> SliderBackend {
+    *SLIDER_BACKEND
+}
+
+#[cfg(all(feature = "force-magic", feature = "force-pext"))]
+compile_error!("Features `force-magic` and `force-pext` can not be enabled at the same time");
+#[cfg(all(feature = "force-pext", not(target_arch = "x86_64")))]
+compile_error!("Feature `force-pext` is supported only on x86_64");
+
+fn detect_slider_backend() -> SliderBackend {
+    if cfg!(feature = "force-magic") {
+        return SliderBackend::Magic;
+    }
+    if cfg!(feature = "force-pext") {
+        // Running PEXT on a CPU that does not have it is undefined behaviour, so forcing it is still checked once
+        assert!(has_bmi2(), "Feature `force-pext` is enabled, but CPU does not support BMI2");
+        return SliderBackend::Pext;
+    }
+    if has_bmi2() {
+        SliderBackend::Pext
+    } else {
+        SliderBackend::Magic
+    }
+}
+
+pub fn has_bmi2() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("bmi2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+#[inline(always)]
+pub fn rook_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    match *SLIDER_BACKEND {
+        // SAFETY: PEXT backend is selected only when BMI2 is supported
+        #[cfg(target_arch = "x86_64")]
+        SliderBackend::Pext => unsafe { pext_bitboards::rook_attacks(square, occupancy) },
+        _ => magic_bitboards::rook_attacks(square, occupancy),
+    }
+}
+#[inline(always)]
+pub fn bishop_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    match *SLIDER_BACKEND {
+        // SAFETY: PEXT backend is selected only when BMI2 is supported
+        #[cfg(target_arch = "x86_64")]
+        SliderBackend::Pext => unsafe { pext_bitboards::bishop_attacks(square, occupancy) },
+        _ => magic_bitboards::bishop_attacks(square, occupancy),
+    }
+}
+#[inline(always)]
+pub fn queen_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    rook_attacks(square, occupancy) | bishop_attacks(square, occupancy)
+}
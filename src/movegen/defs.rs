@@ -15,10 +15,8 @@ pub const G_FILE: Bitboard = 0x4040404040404040;
 pub const H_FILE: Bitboard = 0x8080808080808080;
 
 // Rank bitmasks
-#[allow(unused)]
-const FIRST_RANK: Bitboard = 0x0101010101010101;
-#[allow(unused)]
-const EIGHTH_RANK: Bitboard = 0x8080808080808080;
+pub const FIRST_RANK: Bitboard = 0x00000000000000FF;
+pub const EIGHTH_RANK: Bitboard = 0xFF00000000000000;
  
 // Inverted file bitmasks (to prevent wrapping)
 pub const NOT_A_FILE: Bitboard = !A_FILE;
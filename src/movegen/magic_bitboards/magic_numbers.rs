@@ -0,0 +1,135 @@
+// Generated by `cargo run --release --bin find-magics -- --seed 728364`, do not edit by hand.
+// Attack table size: 107648 entries (841 KiB)
+
+pub const ROOK_MAGIC_NUMBERS: [u64; 64] = [
+    0x2080004000102080,
+    0x00c0001000600045,
+    0x81802000801b5000,
+    0x0100082100100004,
+    0x0480080080040002,
+    0x0200100200040801,
+    0x0480050000800600,
+    0x808001000053a080,
+    0x2420802040008000,
+    0x2402002080410210,
+    0x4402001028420080,
+    0x0032000892004320,
+    0x0101802800040080,
+    0x4003001204000900,
+    0x2047000100220004,
+    0x0f0180010013d080,
+    0x418001c000406005,
+    0x04a0004020401003,
+    0x0022020020108040,
+    0x0021010010000821,
+    0x8808008008040080,
+    0x4012008080020400,
+    0x0002840002411028,
+    0x0080020014008051,
+    0x0000400080008028,
+    0x0820802200410206,
+    0x0040401100200108,
+    0x8008008080100008,
+    0x0000040080800800,
+    0x0220040080800200,
+    0x8000422400102841,
+    0x2801409200104401,
+    0x4480042004400040,
+    0x2190002000400042,
+    0x0060002181805000,
+    0x101d2100b9001000,
+    0x4040800400800800,
+    0x4202008002800400,
+    0x02d4082204000130,
+    0x021000c41a000085,
+    0xc000400080208000,
+    0x0040004081010020,
+    0x24c1004020050010,
+    0x0001001000210008,
+    0x4010080004008080,
+    0x0014010002004040,
+    0x008c020001008080,
+    0x3104008120420004,
+    0x0000400480042480,
+    0xa011020020804200,
+    0x1000100080200080,
+    0x8428100208028080,
+    0x0080800800240280,
+    0x4020800400020080,
+    0x0100110210a80400,
+    0x01000070810c0200,
+    0x410040110200802a,
+    0x8184400100148021,
+    0x00060020100a4082,
+    0x0042004021081006,
+    0x0003004e10380005,
+    0x4001000400080201,
+    0x0600080081021004,
+    0x82380304002088c6,
+];
+pub const BISHOP_MAGIC_NUMBERS: [u64; 64] = [
+    0x0840104880808280,
+    0x0002020441020200,
+    0x0090013043000000,
+    0x2044240190000080,
+    0xc41a0e1000043000,
+    0x160a300420244008,
+    0x0101040202400110,
+    0x00001404010c5012,
+    0x000110e008412040,
+    0x00000404009e2200,
+    0x000809220e120004,
+    0x0c40020a02010040,
+    0x2040011040208402,
+    0x4800091002100680,
+    0x3000104822101000,
+    0x000002210108a010,
+    0x0010012002820812,
+    0x0030022022861044,
+    0x0110401488020040,
+    0x011080a802014020,
+    0x242c000211040005,
+    0x18a0400808021043,
+    0x0484002211440240,
+    0x4001002094809004,
+    0xa010c40008a08400,
+    0x8802084020081080,
+    0x00403014d2008200,
+    0x00040800402020c0,
+    0x0001840048812000,
+    0x00080080880a0101,
+    0x90080040008c1408,
+    0x1408408002460804,
+    0x4101304101080880,
+    0x400410082084a110,
+    0x8440c74800d00020,
+    0x8281200800410104,
+    0x0050020201002008,
+    0x102000908069004d,
+    0xc402042405150084,
+    0x30014a0021120100,
+    0x20020202c1006004,
+    0x240a011002460800,
+    0x008701380a000400,
+    0x0400010411000800,
+    0x0000181101108400,
+    0x0140080080200105,
+    0x0160020200600a02,
+    0x800401006a002100,
+    0x0282021082881080,
+    0x0490805802104410,
+    0x4020008048083010,
+    0x1006100846080100,
+    0x0001004012822000,
+    0x0000423002008628,
+    0x491102302400a000,
+    0x8011010104008020,
+    0x0005008080a84005,
+    0x0000908428921008,
+    0x8600810602010414,
+    0x8000000821040900,
+    0xc808060004250400,
+    0x2280001002880840,
+    0x40d008c204284600,
+    0x0010020801002208,
+];
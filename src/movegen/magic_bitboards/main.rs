@@ -1,5 +1,112 @@
+// Searches for rook and bishop magic numbers and writes them as Rust constants the library includes.
+// Usage: cargo run --release --bin find-magics -- [--seed <u64>] [--output <path>]
+use rusty_chess_engine::gamestate::board::*;
+use rusty_chess_engine::movegen::magic_bitboards::*;
 
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::fmt::Write;
+use std::process::ExitCode;
 
-fn main() {
+const DEFAULT_SEED: u64 = 728364;
+const DEFAULT_OUTPUT: &str = "src/movegen/magic_bitboards/magic_numbers.rs";
 
-}
\ No newline at end of file
+struct Args {
+    seed: u64,
+    output: String,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        seed: DEFAULT_SEED,
+        output: DEFAULT_OUTPUT.to_string(),
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let value = iter.next().ok_or(format!("Missing value for `{}`", arg))?;
+        match arg.as_str() {
+            "--seed" => args.seed = value.parse().map_err(|_| format!("Invalid seed `{}`", value))?,
+            "--output" => args.output = value,
+            _ => return Err(format!("Unknown argument `{}`", arg)),
+        }
+    }
+    Ok(args)
+}
+
+// Finds magics for all of the squares, each of them is checked against ray walking attacks before being accepted
+fn find_magics_for_piece(piece: PieceType, rng: &mut StdRng) -> Result<[Magic; 64], String> {
+    let mut magics = [Magic::default(); 64];
+
+    for (i, magic) in magics.iter_mut().enumerate() {
+        let square = Square::new(i as u8);
+        *magic = find_magic(piece, square, rng);
+        if !verify_magic(piece, square, magic) {
+            return Err(format!("Magic for {:?} on {} failed verification", piece, square.to_algebraic_notation()));
+        }
+    }
+    Ok(magics)
+}
+
+fn table_size(magics: &[Magic; 64]) -> usize {
+    magics.iter().map(|magic| 1usize << (64 - magic.shift)).sum()
+}
+
+fn write_constants(source: &mut String, name: &str, magics: &[Magic; 64]) -> std::fmt::Result {
+    writeln!(source, "pub const {}: [u64; 64] = [", name)?;
+    for magic in magics {
+        writeln!(source, "    {:#018x},", magic.nr)?;
+    }
+    writeln!(source, "];")
+}
+fn generate_source(seed: u64, rook_magics: &[Magic; 64], bishop_magics: &[Magic; 64], total_size: usize, total_kib: usize) -> Result<String, std::fmt::Error> {
+    let mut source = String::new();
+    writeln!(source, "// Generated by `cargo run --release --bin find-magics -- --seed {}`, do not edit by hand.", seed)?;
+    writeln!(source, "// Attack table size: {} entries ({} KiB)", total_size, total_kib)?;
+    writeln!(source)?;
+    write_constants(&mut source, "ROOK_MAGIC_NUMBERS", rook_magics)?;
+    write_constants(&mut source, "BISHOP_MAGIC_NUMBERS", bishop_magics)?;
+    Ok(source)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: find-magics [--seed <u64>] [--output <path>]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let magics = find_magics_for_piece(PieceType::Rook, &mut rng)
+        .and_then(|rook_magics| find_magics_for_piece(PieceType::Bishop, &mut rng).map(|bishop_magics| (rook_magics, bishop_magics)));
+    let (rook_magics, bishop_magics) = match magics {
+        Ok(magics) => magics,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let rook_table_size = table_size(&rook_magics);
+    let bishop_table_size = table_size(&bishop_magics);
+    let total_size = rook_table_size + bishop_table_size;
+    let total_kib = total_size * std::mem::size_of::<Bitboard>() / 1024;
+    println!("Rook table:   {} entries", rook_table_size);
+    println!("Bishop table: {} entries", bishop_table_size);
+    println!("Total:        {} entries ({} KiB)", total_size, total_kib);
+
+    let Ok(source) = generate_source(args.seed, &rook_magics, &bishop_magics, total_size, total_kib) else {
+        eprintln!("Failed to format the magic numbers");
+        return ExitCode::FAILURE;
+    };
+
+    if let Err(e) = std::fs::write(&args.output, source) {
+        eprintln!("Failed to write `{}`: {}", args.output, e);
+        return ExitCode::FAILURE;
+    }
+    println!("Magic numbers written to {}", args.output);
+    ExitCode::SUCCESS
+}
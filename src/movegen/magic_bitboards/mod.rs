@@ -1,12 +1,24 @@
-use crate::gamestate::{
-    board::*,
-    defs::*,
-};
-use super::{
-    defs::*,
-    masks::*,
-    
-};
+pub mod magic_numbers;
+
+use crate::gamestate::board::*;
+use super::masks::*;
+use magic_numbers::*;
+
+use lazy_static::lazy_static;
+use rand::Rng;
+use rand::rngs::StdRng;
+
+// Check https://www.chessprogramming.org/Magic_Bitboards#Fancy for details.
+// Each square has its own magic and its own shift, all of the attacks are stored in one shared table,
+// where offset points to the start of the square's slice.
+// Magic numbers are searched for ahead of time by the find-magics binary, so only the table is filled at startup.
+lazy_static! {
+    static ref MAGIC_TABLES: MagicTables = init_magic_tables();
+}
+
+// (file, rank) steps
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
 
 #[derive(Default, Copy, Clone)]
 pub struct Magic {
@@ -21,4 +33,170 @@ impl Magic {
 
         ((blocker_mask.wrapping_mul(self.nr) >> self.shift) + self.offset) as usize
     }
-}
\ No newline at end of file
+}
+
+struct MagicTables {
+    rook_magics: [Magic; 64],
+    bishop_magics: [Magic; 64],
+    attacks: Vec<Bitboard>,
+}
+
+#[inline(always)]
+pub fn rook_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    let tables: &MagicTables = &MAGIC_TABLES;
+    tables.attacks[tables.rook_magics[square.get_index()].get_index(occupancy)]
+}
+#[inline(always)]
+pub fn bishop_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    let tables: &MagicTables = &MAGIC_TABLES;
+    tables.attacks[tables.bishop_magics[square.get_index()].get_index(occupancy)]
+}
+#[inline(always)]
+pub fn queen_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    rook_attacks(square, occupancy) | bishop_attacks(square, occupancy)
+}
+
+// Slow ray walking versions, used to fill the tables and to verify them
+pub fn rook_attacks_on_the_fly(square: Square, occupancy: Bitboard) -> Bitboard {
+    sliding_attacks(square, occupancy, &ROOK_DIRECTIONS)
+}
+pub fn bishop_attacks_on_the_fly(square: Square, occupancy: Bitboard) -> Bitboard {
+    sliding_attacks(square, occupancy, &BISHOP_DIRECTIONS)
+}
+fn sliding_attacks(square: Square, occupancy: Bitboard, directions: &[(i8, i8); 4]) -> Bitboard {
+    let (file, rank) = square.get_file_rank();
+    let mut attacks: Bitboard = 0;
+
+    for &(file_step, rank_step) in directions {
+        let mut file = file as i8 + file_step;
+        let mut rank = rank as i8 + rank_step;
+
+        while (0..8).contains(&file) && (0..8).contains(&rank) {
+            let mask = Square::new_from_file_rank(file as u8, rank as u8).unwrap().get_mask();
+            attacks |= mask;
+            // Blocker square is still attacked, but nothing behind it is
+            if occupancy & mask != 0 {
+                break;
+            }
+            file += file_step;
+            rank += rank_step;
+        }
+    }
+    attacks
+}
+
+pub fn relevant_occupancy_mask(piece: PieceType, square: Square) -> Bitboard {
+    match piece {
+        PieceType::Rook => ROOK_RELEVANT_OCCUPANCY_MASKS[square.get_index()],
+        PieceType::Bishop => BISHOP_RELEVANT_OCCUPANCY_MASKS[square.get_index()],
+        _ => unreachable!("Magic bitboards are used only for rooks and bishops"),
+    }
+}
+pub fn attacks_on_the_fly(piece: PieceType, square: Square, occupancy: Bitboard) -> Bitboard {
+    match piece {
+        PieceType::Rook => rook_attacks_on_the_fly(square, occupancy),
+        PieceType::Bishop => bishop_attacks_on_the_fly(square, occupancy),
+        _ => unreachable!("Magic bitboards are used only for rooks and bishops"),
+    }
+}
+// All subsets of the mask, using Carry-Rippler trick
+// Check https://www.chessprogramming.org/Traversing_Subsets_of_a_Set
+pub fn occupancy_subsets(mask: Bitboard) -> Vec<Bitboard> {
+    let mut subsets = Vec::with_capacity(1 << mask.count_ones());
+    let mut subset: Bitboard = 0;
+    loop {
+        subsets.push(subset);
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+    subsets
+}
+
+// Searches for a magic number by trial and error, returned magic has offset of 0
+pub fn find_magic(piece: PieceType, square: Square, rng: &mut StdRng) -> Magic {
+    let mask = relevant_occupancy_mask(piece, square);
+    let bits = mask.count_ones();
+    let occupancies = occupancy_subsets(mask);
+    let reference_attacks: Vec<Bitboard> = occupancies.iter()
+        .map(|&occupancy| attacks_on_the_fly(piece, square, occupancy))
+        .collect();
+
+    // Instead of clearing the table after every failed attempt, entries are stamped with the attempt they were written at
+    let mut used: Vec<(u32, Bitboard)> = vec![(0, 0); 1 << bits];
+    let mut attempt: u32 = 0;
+    loop {
+        // Numbers with a low amount of set bits make better magics
+        let nr: u64 = rng.gen::<u64>() & rng.gen::<u64>() & rng.gen::<u64>();
+        if (mask.wrapping_mul(nr) & 0xFF00_0000_0000_0000).count_ones() < 6 {
+            continue;
+        }
+        attempt += 1;
+
+        let magic = magic_from_number(piece, square, nr);
+        let mut is_valid = true;
+        for i in 0..occupancies.len() {
+            let entry = &mut used[magic.get_index(occupancies[i])];
+            if entry.0 != attempt {
+                *entry = (attempt, reference_attacks[i]);
+            } else if entry.1 != reference_attacks[i] {
+                // Collision is fine only if it gives the same attacks
+                is_valid = false;
+                break;
+            }
+        }
+        if is_valid {
+            return magic;
+        }
+    }
+}
+
+// Checks the magic against ray walking attacks for every subset of its mask
+pub fn verify_magic(piece: PieceType, square: Square, magic: &Magic) -> bool {
+    let mut used: Vec<Option<Bitboard>> = vec![None; 1 << (64 - magic.shift)];
+
+    occupancy_subsets(magic.mask).into_iter().all(|occupancy| {
+        let attacks = attacks_on_the_fly(piece, square, occupancy);
+        let entry = &mut used[magic.get_index(occupancy) - magic.offset as usize];
+        match entry {
+            Some(stored) => *stored == attacks,
+            None => {
+                *entry = Some(attacks);
+                true
+            },
+        }
+    })
+}
+pub fn magic_from_number(piece: PieceType, square: Square, nr: u64) -> Magic {
+    let mask = relevant_occupancy_mask(piece, square);
+    Magic { mask, shift: (64 - mask.count_ones()) as u8, offset: 0, nr }
+}
+
+fn init_magic_tables() -> MagicTables {
+    let mut attacks: Vec<Bitboard> = Vec::new();
+
+    let rook_magics = init_magics_for_piece(PieceType::Rook, &ROOK_MAGIC_NUMBERS, &mut attacks);
+    let bishop_magics = init_magics_for_piece(PieceType::Bishop, &BISHOP_MAGIC_NUMBERS, &mut attacks);
+
+    MagicTables {
+        rook_magics,
+        bishop_magics,
+        attacks,
+    }
+}
+fn init_magics_for_piece(piece: PieceType, magic_numbers: &[u64; 64], attacks: &mut Vec<Bitboard>) -> [Magic; 64] {
+    let mut magics = [Magic::default(); 64];
+
+    for (i, magic) in magics.iter_mut().enumerate() {
+        let square = Square::new(i as u8);
+        *magic = magic_from_number(piece, square, magic_numbers[i]);
+        magic.offset = attacks.len() as u64;
+
+        attacks.resize(attacks.len() + (1 << (64 - magic.shift)), 0);
+        for occupancy in occupancy_subsets(magic.mask) {
+            attacks[magic.get_index(occupancy)] = attacks_on_the_fly(piece, square, occupancy);
+        }
+    }
+    magics
+}
@@ -19,6 +19,17 @@ pub const BLACK_PAWN_PUSHES_MASKS: [Bitboard; 64] = generate_pawn_pushes_masks()
 pub const ROOK_RAYS: [Bitboard; 64] = generate_rook_rays();
 pub const BISHOP_RAYS: [Bitboard; 64] = generate_bishop_rays();
 
+// Rays without the edge squares, this are the only squares that can block a slider.
+// Used as magic bitboards masks, check https://www.chessprogramming.org/Magic_Bitboards
+pub const ROOK_RELEVANT_OCCUPANCY_MASKS: [Bitboard; 64] = generate_rook_relevant_occupancy_masks();
+pub const BISHOP_RELEVANT_OCCUPANCY_MASKS: [Bitboard; 64] = generate_bishop_relevant_occupancy_masks();
+
+// Squares strictly between two squares and the full line thru them, both are empty if squares are not aligned.
+// Indexed by [from][to], used for pins and check evasions.
+// This are statics instead of constants, since copying 32Kb table on every use is not that great
+pub static BETWEEN_MASKS: [[Bitboard; 64]; 64] = generate_between_masks();
+pub static LINE_MASKS: [[Bitboard; 64]; 64] = generate_line_masks();
+
 
 //pub const ROOK_RAYS: [Bitboard; 64] = generate_rook_rays();
 //pub const BISHOP_RAYS: [Bitboard; 64] = generate_bishop_rays();
@@ -27,7 +38,7 @@ const fn generate_knight_attacks_masks() -> [Bitboard; 64] {
     let mut all_attacks: [Bitboard; 64] = [0; 64];
 
     let mut i: usize = 0;
-    while i < 63 {
+    while i < 64 {
         let mut attacks_mask: Bitboard = 0;
         let position_mask = Square::new(i as u8).get_mask();
 
@@ -109,13 +120,13 @@ const fn generate_pawn_pushes_masks()  -> ([Bitboard; 64], [Bitboard; 64]) {
         let rank = Square::new(i as u8).get_file_rank().1;
 
         white_pushes_mask |= position_mask << NORTH;
-        if rank == 1 { // Second file applies 2 square move
+        if rank == 1 { // Second rank applies 2 square move
             white_pushes_mask |= position_mask << (NORTH * 2);
         }
 
         black_pushes_mask |= position_mask >> -SOUTH;
-        if rank == 6 { // Fifth file applies 2 square move
-            white_pushes_mask |= position_mask >> (-SOUTH * 2);
+        if rank == 6 { // Seventh rank applies 2 square move
+            black_pushes_mask |= position_mask >> (-SOUTH * 2);
         }
         
         all_white_pushes[i] = white_pushes_mask;
@@ -209,3 +220,114 @@ const fn generate_bishop_rays() -> [Bitboard; 64] {
     all_rays
 }
 
+const fn generate_rook_relevant_occupancy_masks() -> [Bitboard; 64] {
+    let mut all_masks: [Bitboard; 64] = [0; 64];
+
+    let mut i: usize = 0;
+    while i < 64 {
+        let (file, rank) = Square::new(i as u8).get_file_rank();
+
+        // Edges are excluded only when the rook is not standing on them, 
+        // otherwise the ray along the edge would lose all of its squares
+        let mut edges: Bitboard = 0;
+        if rank != 0 {
+            edges |= FIRST_RANK;
+        }
+        if rank != 7 {
+            edges |= EIGHTH_RANK;
+        }
+        if file != 0 {
+            edges |= A_FILE;
+        }
+        if file != 7 {
+            edges |= H_FILE;
+        }
+
+        all_masks[i] = ROOK_RAYS[i] & !edges;
+        i += 1;
+    }
+
+    all_masks
+}
+const fn generate_bishop_relevant_occupancy_masks() -> [Bitboard; 64] {
+    let mut all_masks: [Bitboard; 64] = [0; 64];
+    let edges: Bitboard = FIRST_RANK | EIGHTH_RANK | A_FILE | H_FILE;
+
+    let mut i: usize = 0;
+    while i < 64 {
+        all_masks[i] = BISHOP_RAYS[i] & !edges;
+        i += 1;
+    }
+
+    all_masks
+}
+
+// (file, rank) step that leads from one square to another, (0, 0) if they are not on the same line
+const fn get_direction(from: usize, to: usize) -> (i32, i32) {
+    let file_diff = (to % 8) as i32 - (from % 8) as i32;
+    let rank_diff = (to / 8) as i32 - (from / 8) as i32;
+
+    if from == to {
+        (0, 0)
+    } else if file_diff == 0 || rank_diff == 0 || file_diff.abs() == rank_diff.abs() {
+        (file_diff.signum(), rank_diff.signum())
+    } else {
+        (0, 0)
+    }
+}
+const fn generate_between_masks() -> [[Bitboard; 64]; 64] {
+    let mut all_masks: [[Bitboard; 64]; 64] = [[0; 64]; 64];
+
+    let mut from: usize = 0;
+    while from < 64 {
+        let mut to: usize = 0;
+        while to < 64 {
+            let (file_step, rank_step) = get_direction(from, to);
+            if file_step != 0 || rank_step != 0 {
+                let mut mask: Bitboard = 0;
+                let mut pos = from as i32 + file_step + rank_step * 8;
+                while pos != to as i32 {
+                    mask |= 1 << pos;
+                    pos += file_step + rank_step * 8;
+                }
+                all_masks[from][to] = mask;
+            }
+            to += 1;
+        }
+        from += 1;
+    }
+
+    all_masks
+}
+const fn generate_line_masks() -> [[Bitboard; 64]; 64] {
+    let mut all_masks: [[Bitboard; 64]; 64] = [[0; 64]; 64];
+
+    let mut from: usize = 0;
+    while from < 64 {
+        let mut to: usize = 0;
+        while to < 64 {
+            let (file_step, rank_step) = get_direction(from, to);
+            if file_step != 0 || rank_step != 0 {
+                let mut mask: Bitboard = 1 << from;
+
+                // Walk both ways from the first square until the edge of the board
+                let mut direction: i32 = 1;
+                while direction >= -1 {
+                    let mut file = (from % 8) as i32 + file_step * direction;
+                    let mut rank = (from / 8) as i32 + rank_step * direction;
+                    while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+                        mask |= 1 << (rank * 8 + file);
+                        file += file_step * direction;
+                        rank += rank_step * direction;
+                    }
+                    direction -= 2;
+                }
+                all_masks[from][to] = mask;
+            }
+            to += 1;
+        }
+        from += 1;
+    }
+
+    all_masks
+}
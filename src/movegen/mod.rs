@@ -1,10 +1,19 @@
 pub mod defs;
 pub mod magic_bitboards;
+#[cfg(target_arch = "x86_64")]
+pub mod pext_bitboards;
+pub mod attacks;
 pub mod masks;
+pub mod move_list;
+pub mod perft;
+
+pub use attacks::{rook_attacks, bishop_attacks, queen_attacks};
+pub use move_list::MoveList;
 
 use crate::gamestate::{
     board::*,
     castling_rights::*,
+    zobrist::*,
     Gamestate,
     Move, 
     MoveFlags, 
@@ -12,11 +21,41 @@ use crate::gamestate::{
 };
 use defs::*;
 use masks::*;
-use rayon::prelude::*;
 
 
+// Type of the moves to generate. Captures and quiets split all of the legal moves in two,
+// so search can look at the captures first and generate the rest only when it has to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenType {
+    // Every legal move
+    All,
+    // Captures, including en passant and promotion captures, and queen promotions
+    Captures,
+    // Everything that is not in captures: quiet moves, double pushes, castling and quiet underpromotions
+    Quiets,
+    // Every legal move when own king is in check: king moves, captures of the checking piece and blocks
+    Evasions,
+    // Quiet moves that give check, directly or by discovering a slider, including castling and underpromotions
+    QuietChecks,
+}
+impl GenType {
+    #[inline(always)]
+    fn has_captures(self) -> bool {
+        !matches!(self, GenType::Quiets | GenType::QuietChecks)
+    }
+    #[inline(always)]
+    fn has_quiets(self) -> bool {
+        self != GenType::Captures
+    }
+}
+
+struct CheckInfo {
+    king_square: Square,
+    discoverers: Bitboard,
+}
+
 pub struct MoveGen<'a>{
-    game: &'a mut Gamestate,
+    game: &'a Gamestate,
     combined_occupancy: Bitboard,
     white_occupancy: Bitboard,
     black_occupancy: Bitboard,
@@ -26,14 +65,9 @@ pub struct MoveGen<'a>{
 // any function that starts with get - gives you a pseudo legal moves, and, any that starts with generete - gives you legal moves.
 impl<'a> MoveGen<'a> {
 
-    pub fn new(game: &'a mut Gamestate) -> Self {   
-        let white_occupancy: Bitboard = game.board.white_pieces.par_iter()
-            .fold(|| 0, |acc, &piece| acc | piece)
-            .reduce(|| 0, |acc, piece| acc | piece);
-
-        let black_occupancy: Bitboard = game.board.black_pieces.par_iter()
-            .fold(|| 0, |acc, &piece| acc | piece)
-            .reduce(|| 0, |acc, piece| acc | piece);
+    pub fn new(game: &'a Gamestate) -> Self {   
+        let white_occupancy: Bitboard = game.board.get_occupancy(Side::White);
+        let black_occupancy: Bitboard = game.board.get_occupancy(Side::Black);
 
         let combined_occupancy: Bitboard = white_occupancy | black_occupancy;
 
@@ -45,111 +79,476 @@ impl<'a> MoveGen<'a> {
         }
     }
 
-    pub fn gererate(&self) -> Vec<Move> {
-        let mut moves = Vec::new();
-        //moves.extend(self.generate_king_moves());
-        moves.extend(self.get_pawn_moves());
+    pub fn gererate(&self) -> MoveList {
+        self.generate_of_type(GenType::All)
+    }
+    pub fn generate_captures(&self) -> MoveList {
+        self.generate_of_type(GenType::Captures)
+    }
+    pub fn generate_quiets(&self) -> MoveList {
+        self.generate_of_type(GenType::Quiets)
+    }
+    // Position has to be in check
+    pub fn generate_evasions(&self) -> MoveList {
+        self.generate_of_type(GenType::Evasions)
+    }
+    pub fn generate_quiet_checks(&self) -> MoveList {
+        self.generate_of_type(GenType::QuietChecks)
+    }
+    pub fn generate_of_type(&self, gen_type: GenType) -> MoveList {
+        let mut moves = MoveList::new();
+        let enemy_occupancy = match self.game.side_to_move {
+            Side::White => self.black_occupancy,
+            Side::Black => self.white_occupancy,
+        };
+        let capture_targets = if gen_type.has_captures() { enemy_occupancy } else { 0 };
+        let quiet_targets = if gen_type.has_quiets() { !self.combined_occupancy } else { 0 };
+
+        // King escapes check by itself, so only the other pieces are limited to the evasion squares
+        self.get_king_moves(&mut moves, capture_targets, quiet_targets);
+
+        let evasion_mask = match gen_type {
+            GenType::Evasions => self.get_evasion_mask(),
+            _ => !0,
+        };
+        // In double check nothing but the king can move
+        if evasion_mask != 0 {
+            let capture_targets = capture_targets & evasion_mask;
+            let quiet_targets = quiet_targets & evasion_mask;
+            self.get_knight_moves(&mut moves, capture_targets, quiet_targets);
+            self.get_pawn_moves(&mut moves, gen_type, evasion_mask);
+            if gen_type.has_captures() {
+                self.get_en_passant_moves(&mut moves);
+            }
+            self.get_bishop_moves(&mut moves, capture_targets, quiet_targets);
+            self.get_rook_moves(&mut moves, capture_targets, quiet_targets);
+            self.get_queen_moves(&mut moves, capture_targets, quiet_targets);
+        }
+        // King can not castle out of check
+        if matches!(gen_type, GenType::All | GenType::Quiets | GenType::QuietChecks) {
+            self.get_castling_moves(&mut moves);
+        }
+        if gen_type == GenType::QuietChecks {
+            match self.get_check_info() {
+                Some(check_info) => moves.retain(|mov| self.gives_check_with_info(mov, &check_info)),
+                None => moves.clear(),
+            }
+        }
+        self.filter_valid_moves(&mut moves);
         moves
     }
+    // Tells if the move, legal or at least pseudo legal, gives check to the enemy king without making it
+    pub fn gives_check(&self, mov: &Move) -> bool {
+        match self.get_check_info() {
+            Some(check_info) => self.gives_check_with_info(mov, &check_info),
+            None => false,
+        }
+    }
+    // Squares that get the king out of a single check: the checking piece and the squares between it and the king
+    fn get_evasion_mask(&self) -> Bitboard {
+        let checkers = self.game.checkers;
+        assert!(checkers != 0, "Evasions can only be generated when the king is in check");
+        if checkers.count_ones() > 1 {
+            return 0;
+        }
+        let king_square = self.get_king_square();
+        let checker_square = Square::new(checkers.trailing_zeros() as u8);
+        checkers | BETWEEN_MASKS[king_square.get_index()][checker_square.get_index()]
+    }
 
-    fn filter_valid_moves(&self, mut moves: &Vec<Move>) {
-        todo!()
+    // Validation of the moves that did not come from the generator for this position (transposition table, killer moves, GUI),
+    // without generating all of the moves. Pseudo legal move can still leave own king in check, legal one can not
+    pub fn is_legal(&self, mov: &Move) -> bool {
+        if !self.is_pseudo_legal(mov) {
+            return false;
+        }
+        let king_square = self.get_king_square();
+        let pinned = self.get_pinned_pieces(king_square);
+        self.is_valid_move(mov, king_square, self.game.checkers, pinned)
     }
+    pub fn is_pseudo_legal(&self, mov: &Move) -> bool {
+        let Some(flags) = mov.try_get_flags() else {
+            return false;
+        };
+        let (from, to) = (mov.get_from_square(), mov.get_to_square());
+        let side = self.game.side_to_move;
+        let piece = match self.game.board.get_piece_at_square(from) {
+            Some((piece, piece_side)) if piece_side == side => piece,
+            _ => return false,
+        };
 
-    fn get_king_moves(&self) -> Vec<Move> {
-        self.get_basic_moves_for_pieces(PieceType::King, &KING_ATTAKS_MASKS)
+        // There are at most two castling and two en passant moves, so it is simpler to generate them and look the move up
+        if mov.is_castle() || flags == MoveFlags::EpCapture {
+            let mut special_moves = MoveList::new();
+            if flags == MoveFlags::EpCapture {
+                self.get_en_passant_moves(&mut special_moves);
+            } else {
+                self.get_castling_moves(&mut special_moves);
+            }
+            return special_moves.contains(mov);
+        }
+
+        // Capture has to land on an enemy piece, and any other move on an empty square
+        let enemy_occupancy = match side {
+            Side::White => self.black_occupancy,
+            Side::Black => self.white_occupancy,
+        };
+        let to_mask = to.get_mask();
+        let target_matches_flags = if mov.is_capture() {
+            enemy_occupancy & to_mask != 0
+        } else {
+            self.combined_occupancy & to_mask == 0
+        };
+        if !target_matches_flags {
+            return false;
+        }
+
+        let attacks = match piece {
+            PieceType::Pawn => return self.is_pseudo_legal_pawn_move(mov, from, to),
+            // Pieces other than pawns have nothing but quiet moves and captures
+            _ if !matches!(flags, MoveFlags::Quiet | MoveFlags::Capture) => return false,
+            PieceType::Knight => KNIGHT_ATTACKS_MASKS[from.get_index()],
+            PieceType::King => KING_ATTAKS_MASKS[from.get_index()],
+            PieceType::Bishop => bishop_attacks(from, self.combined_occupancy),
+            PieceType::Rook => rook_attacks(from, self.combined_occupancy),
+            PieceType::Queen => queen_attacks(from, self.combined_occupancy),
+        };
+        attacks & to_mask != 0
     }
-    fn get_queen_moves(&self) -> Vec<Move> {
-        // Implement queen move generation logic here
-        todo!()
+    // Target square is already known to match the capture flag
+    fn is_pseudo_legal_pawn_move(&self, mov: &Move, from: Square, to: Square) -> bool {
+        // Pawns can never move backwards, so any of the edge ranks is the promotion one, and moving there has to be a promotion
+        let is_promotion_rank = to.get_mask() & (FIRST_RANK | EIGHTH_RANK) != 0;
+        if mov.is_promotion() != is_promotion_rank {
+            return false;
+        }
+
+        let from_mask = from.get_mask();
+        let (attacks_masks, pushes_masks, single_push_mask) = match self.game.side_to_move {
+            Side::White => (&WHITE_PAWN_ATTACKS_MASKS, &WHITE_PAWN_PUSHES_MASKS, from_mask << NORTH),
+            Side::Black => (&BLACK_PAWN_ATTACKS_MASKS, &BLACK_PAWN_PUSHES_MASKS, from_mask >> -SOUTH),
+        };
+        match mov.get_flags() {
+            MoveFlags::DoublePawnPush => {
+                let double_push_mask = pushes_masks[from.get_index()] & !single_push_mask;
+                single_push_mask & self.combined_occupancy == 0 && double_push_mask == to.get_mask()
+            },
+            _ if mov.is_capture() => attacks_masks[from.get_index()] & to.get_mask() != 0,
+            _ => single_push_mask == to.get_mask(),
+        }
     }
-    fn get_rook_moves(&self) -> Vec<Move> {
-        // Implement rook move generation logic here
-        todo!()
+
+    // Keeps only the moves that do not leave own king in check. Instead of making every move, 
+    // it uses pinned pieces and the pieces giving check, check https://www.chessprogramming.org/Checks_and_Pinned_Pieces_(Bitboards)
+    fn filter_valid_moves(&self, moves: &mut MoveList) {
+        let king_square = self.get_king_square();
+        let checkers = self.game.checkers;
+        let pinned = self.get_pinned_pieces(king_square);
+
+        moves.retain(|mov| self.is_valid_move(mov, king_square, checkers, pinned));
     }
-    fn get_bishop_moves(&self) -> Vec<Move> {
-        // Implement bishop move generation logic here
-        todo!()
+    fn is_valid_move(&self, mov: &Move, king_square: Square, checkers: Bitboard, pinned: Bitboard) -> bool {
+        let (flags, from, to) = mov.decode();
+        let opponent = self.game.side_to_move.opposite();
+
+        // Castling generation already checks every square on the king's path
+        if mov.is_castle() {
+            return true;
+        }
+        if from == king_square {
+            // King is removed from the occupancy, so it can not hide behind itself from a slider
+            let occupancy = self.combined_occupancy & !king_square.get_mask();
+            return !self.game.board.is_square_attacked(to, opponent, occupancy);
+        }
+        // Only king can escape double check
+        if checkers.count_ones() > 1 {
+            return false;
+        }
+        if flags == MoveFlags::EpCapture {
+            // Two pawns leave the same rank at once, which can discover an attack that pins do not catch,
+            // so the position after the capture is checked directly
+            let (file, _) = to.get_file_rank();
+            let (_, rank) = from.get_file_rank();
+            let captured_square = Square::new_from_file_rank(file, rank).unwrap();
+            let occupancy = (self.combined_occupancy & !from.get_mask() & !captured_square.get_mask()) | to.get_mask();
+            return !self.game.board.is_square_attacked(king_square, opponent, occupancy);
+        }
+        if checkers != 0 {
+            // Single check has to be blocked or the checking piece has to be captured
+            let checker_square = Square::new(checkers.trailing_zeros() as u8);
+            let evasion_mask = checkers | BETWEEN_MASKS[king_square.get_index()][checker_square.get_index()];
+            if to.get_mask() & evasion_mask == 0 {
+                return false;
+            }
+        }
+        // Pinned piece can only move along the line of the pin
+        if pinned & from.get_mask() != 0 {
+            return LINE_MASKS[king_square.get_index()][from.get_index()] & to.get_mask() != 0;
+        }
+        true
+    }
+
+    fn get_king_square(&self) -> Square {
+        let side = self.game.side_to_move;
+        self.game.board.get_king_square(side)
+            .unwrap_or_else(|| panic!("There is no king on the board for {:?}", side))
     }
-    fn get_knight_moves(&self) -> Vec<Move> {
-        self.get_basic_moves_for_pieces(PieceType::Knight, &KNIGHT_ATTACKS_MASKS)
+    // Own pieces that are the only thing between own king and enemy slider
+    fn get_pinned_pieces(&self, king_square: Square) -> Bitboard {
+        self.get_blockers(king_square, self.game.side_to_move.opposite()) & self.get_own_occupancy()
     }
-    fn get_pawn_moves(&self) -> Vec<Move> {
-        let mut moves: Vec<Move> = Vec::new();
+    // Pieces of any side that are the only thing between the king and a slider of the given side
+    fn get_blockers(&self, king_square: Square, sliders_side: Side) -> Bitboard {
+        let sliders = self.game.board.get_pieces(sliders_side);
+        let rooks_queens = sliders[PieceType::Rook as usize] | sliders[PieceType::Queen as usize];
+        let bishops_queens = sliders[PieceType::Bishop as usize] | sliders[PieceType::Queen as usize];
+
+        // Sliders that would attack the king on an empty board
+        let snipers = (rook_attacks(king_square, 0) & rooks_queens) | (bishop_attacks(king_square, 0) & bishops_queens);
+
+        let mut blockers: Bitboard = 0;
+        for sniper in BitboardIter::new(snipers) {
+            let between = BETWEEN_MASKS[king_square.get_index()][sniper.get_index()] & self.combined_occupancy;
+            if between.count_ones() == 1 {
+                blockers |= between;
+            }
+        }
+        blockers
+    }
+    #[inline(always)]
+    fn get_own_occupancy(&self) -> Bitboard {
         match self.game.side_to_move {
-            Side::White => {
-                moves.extend(self.get_capture_moves_for_pieces(PieceType::Pawn, &WHITE_PAWN_ATTACKS_MASKS));
-                moves.extend(self.get_quiet_moves_for_pieces(PieceType::Pawn, &WHITE_PAWN_PUSHES_MASKS));
-            },
-            Side::Black => {
-                moves.extend(self.get_capture_moves_for_pieces(PieceType::Pawn, &BLACK_PAWN_ATTACKS_MASKS));
-                moves.extend(self.get_quiet_moves_for_pieces(PieceType::Pawn, &BLACK_PAWN_PUSHES_MASKS));
-            },
-            
+            Side::White => self.white_occupancy,
+            Side::Black => self.black_occupancy,
+        }
+    }
+
+    // Enemy king square and own pieces that discover a check by moving off the line to it.
+    // There is nothing to check if enemy king is not on the board
+    fn get_check_info(&self) -> Option<CheckInfo> {
+        let side = self.game.side_to_move;
+        let king_square = self.game.board.get_king_square(side.opposite())?;
+        let discoverers = self.get_blockers(king_square, side) & self.get_own_occupancy();
+        Some(CheckInfo { king_square, discoverers })
+    }
+    // Check is found by looking from the destination square with the occupancy after the move,
+    // check https://www.chessprogramming.org/Checks_and_Pinned_Pieces_(Bitboards) for details
+    fn gives_check_with_info(&self, mov: &Move, check_info: &CheckInfo) -> bool {
+        let (flags, from, to) = mov.decode();
+        let side = self.game.side_to_move;
+        let king_square = check_info.king_square;
+        let king_mask = king_square.get_mask();
+
+        if mov.is_castle() {
+            // King itself never gives check, so only the rook on its new square has to be looked at
+            let (rook_from, rook_to) = match (side, flags) {
+                (Side::White, MoveFlags::KingCastle) => (WHITE_ROOK_KINGSIDE_STATING_INDEX, WHITE_ROOK_KINGSIDE_END_INDEX),
+                (Side::White, _) => (WHITE_ROOK_QUEENSIDE_STARTING_INDEX, WHITE_ROOK_QUEENSIDE_END_INDEX),
+                (Side::Black, MoveFlags::KingCastle) => (BLACK_ROOK_KINGSIDE_STATING_INDEX, BLACK_ROOK_KINGSIDE_END_INDEX),
+                (Side::Black, _) => (BLACK_ROOK_QUEENSIDE_STARTING_INDEX, BLACK_ROOK_QUEENSIDE_END_INDEX),
+            };
+            let occupancy = (self.combined_occupancy & !from.get_mask() & !(1 << rook_from)) | to.get_mask() | (1 << rook_to);
+            return rook_attacks(Square::new(rook_to), occupancy) & king_mask != 0;
+        }
+
+        let mut occupancy = (self.combined_occupancy & !from.get_mask()) | to.get_mask();
+        let piece = match flags.get_promotion_piece() {
+            Some(promotion_piece) => promotion_piece,
+            None => self.game.board.get_piece_at_square(from).expect("Move from an empty square").0,
+        };
+        let pawn_attacks_masks = match side {
+            Side::White => &WHITE_PAWN_ATTACKS_MASKS,
+            Side::Black => &BLACK_PAWN_ATTACKS_MASKS,
+        };
+        let direct_attacks = match piece {
+            PieceType::Pawn => pawn_attacks_masks[to.get_index()],
+            PieceType::Knight => KNIGHT_ATTACKS_MASKS[to.get_index()],
+            PieceType::Bishop => bishop_attacks(to, occupancy),
+            PieceType::Rook => rook_attacks(to, occupancy),
+            PieceType::Queen => queen_attacks(to, occupancy),
+            PieceType::King => 0,
+        };
+        if direct_attacks & king_mask != 0 {
+            return true;
+        }
+
+        if flags == MoveFlags::EpCapture {
+            // Captured pawn leaves the board as well, so it can discover a check that is not in the discoverers
+            let (file, _) = to.get_file_rank();
+            let (_, rank) = from.get_file_rank();
+            occupancy &= !Square::new_from_file_rank(file, rank).unwrap().get_mask();
+
+            let own_pieces = self.game.board.get_pieces(side);
+            let rooks_queens = own_pieces[PieceType::Rook as usize] | own_pieces[PieceType::Queen as usize];
+            let bishops_queens = own_pieces[PieceType::Bishop as usize] | own_pieces[PieceType::Queen as usize];
+            return (rook_attacks(king_square, occupancy) & rooks_queens) | (bishop_attacks(king_square, occupancy) & bishops_queens) != 0;
+        }
+        // Discoverer has to leave the line between the slider and the enemy king
+        check_info.discoverers & from.get_mask() != 0
+            && LINE_MASKS[king_square.get_index()][from.get_index()] & to.get_mask() == 0
+    }
+
+    // Pieces moves are generated only to the target squares, the ones for captures have to be a subset of enemy pieces,
+    // and the ones for quiet moves a subset of empty squares
+    fn get_king_moves(&self, moves: &mut MoveList, capture_targets: Bitboard, quiet_targets: Bitboard) {
+        self.get_basic_moves_for_pieces(moves, PieceType::King, &KING_ATTAKS_MASKS, capture_targets, quiet_targets)
+    }
+    fn get_queen_moves(&self, moves: &mut MoveList, capture_targets: Bitboard, quiet_targets: Bitboard) {
+        self.get_sliding_moves_for_pieces(moves, PieceType::Queen, queen_attacks, capture_targets, quiet_targets)
+    }
+    fn get_rook_moves(&self, moves: &mut MoveList, capture_targets: Bitboard, quiet_targets: Bitboard) {
+        self.get_sliding_moves_for_pieces(moves, PieceType::Rook, rook_attacks, capture_targets, quiet_targets)
+    }
+    fn get_bishop_moves(&self, moves: &mut MoveList, capture_targets: Bitboard, quiet_targets: Bitboard) {
+        self.get_sliding_moves_for_pieces(moves, PieceType::Bishop, bishop_attacks, capture_targets, quiet_targets)
+    }
+    fn get_knight_moves(&self, moves: &mut MoveList, capture_targets: Bitboard, quiet_targets: Bitboard) {
+        self.get_basic_moves_for_pieces(moves, PieceType::Knight, &KNIGHT_ATTACKS_MASKS, capture_targets, quiet_targets)
+    }
+    // Promotions do not fit into the target squares, since queen promotion goes with captures even if it is quiet,
+    // so pawns get the type of generation instead
+    fn get_pawn_moves(&self, moves: &mut MoveList, gen_type: GenType, evasion_mask: Bitboard) {
+        let (pawns, enemy_occupancy, attacks_masks, pushes_masks) = match self.game.side_to_move {
+            Side::White => (self.game.board.white_pieces[PieceType::Pawn as usize], self.black_occupancy, &WHITE_PAWN_ATTACKS_MASKS, &WHITE_PAWN_PUSHES_MASKS),
+            Side::Black => (self.game.board.black_pieces[PieceType::Pawn as usize], self.white_occupancy, &BLACK_PAWN_ATTACKS_MASKS, &BLACK_PAWN_PUSHES_MASKS),
+        };
+
+        for from in BitboardIter::new(pawns) {
+            let from_mask = from.get_mask();
+            let single_push_mask = match self.game.side_to_move {
+                Side::White => from_mask << NORTH,
+                Side::Black => from_mask >> -SOUTH,
+            };
+
+            // Pushes mask has both single and double push, but double push is possible only if the single one is
+            if single_push_mask & self.combined_occupancy == 0 {
+                if single_push_mask & evasion_mask != 0 {
+                    let single_push = Square::new(single_push_mask.trailing_zeros() as u8);
+                    Self::add_pawn_moves(moves, from, single_push, false, gen_type);
+                }
+
+                let double_push_mask = pushes_masks[from.get_index()] & !single_push_mask & !self.combined_occupancy & evasion_mask;
+                if gen_type.has_quiets() && double_push_mask != 0 {
+                    moves.push(Move::encode(from, Square::new(double_push_mask.trailing_zeros() as u8), MoveFlags::DoublePawnPush));
+                }
+            }
+
+            if gen_type.has_captures() {
+                for capture in BitboardIter::new(attacks_masks[from.get_index()] & enemy_occupancy & evasion_mask) {
+                    Self::add_pawn_moves(moves, from, capture, true, gen_type);
+                }
+            }
+        }
+    }
+    // Pawn reaching the last rank has to be promoted, so instead of one move there are four
+    #[inline(always)]
+    fn add_pawn_moves(moves: &mut MoveList, from: Square, to: Square, is_capture: bool, gen_type: GenType) {
+        // Pawns can never move backwards, so any of the edge ranks is the promotion one
+        if to.get_mask() & (FIRST_RANK | EIGHTH_RANK) == 0 {
+            if is_capture {
+                moves.push(Move::encode(from, to, MoveFlags::Capture));
+            } else if gen_type.has_quiets() {
+                moves.push(Move::encode(from, to, MoveFlags::Quiet));
+            }
+            return;
+        }
+
+        if is_capture {
+            for flags in [MoveFlags::QueenPromoCapture, MoveFlags::RookPromoCapture, MoveFlags::BishopPromoCapture, MoveFlags::KnightPromoCapture] {
+                moves.push(Move::encode(from, to, flags));
+            }
+            return;
+        }
+        // Queen promotion changes the material as much as a capture does, so it is searched together with them.
+        // Quiet checks are the exception, they take every checking promotion
+        if gen_type.has_captures() || gen_type == GenType::QuietChecks {
+            moves.push(Move::encode(from, to, MoveFlags::QueenPromotion));
+        }
+        if gen_type.has_quiets() {
+            for flags in [MoveFlags::RookPromotion, MoveFlags::BishopPromotion, MoveFlags::KnightPromotion] {
+                moves.push(Move::encode(from, to, flags));
+            }
         }
-        return moves;
     }
-    fn get_castling_moves(&self) -> Vec<Move> {
-        // Implement castling move generation logic here
-        todo!()
+    // Castling moves are generated already legal, since the rules for them are stricter than for the other king moves:
+    // king can not castle out of, thru or into check
+    fn get_castling_moves(&self, moves: &mut MoveList) {
+        let side = self.game.side_to_move;
+        let rights = self.game.castling_rights.get_for_side(side);
+        let (king_start, rook_kingside_start, king_kingside_end, rook_queenside_start, king_queenside_end) = match side {
+            Side::White => (WHITE_KING_STARTING_INDEX, WHITE_ROOK_KINGSIDE_STATING_INDEX, WHITE_KING_KINGSIDE_END_INDEX, WHITE_ROOK_QUEENSIDE_STARTING_INDEX, WHITE_KING_QUEENSIDE_END_INDEX),
+            Side::Black => (BLACK_KING_STARTING_INDEX, BLACK_ROOK_KINGSIDE_STATING_INDEX, BLACK_KING_KINGSIDE_END_INDEX, BLACK_ROOK_QUEENSIDE_STARTING_INDEX, BLACK_KING_QUEENSIDE_END_INDEX),
+        };
+
+        if rights.has_kingside() && self.can_castle(king_start, rook_kingside_start, king_kingside_end) {
+            moves.push(Move::encode(Square::new(king_start), Square::new(king_kingside_end), MoveFlags::KingCastle));
+        }
+        if rights.has_queenside() && self.can_castle(king_start, rook_queenside_start, king_queenside_end) {
+            moves.push(Move::encode(Square::new(king_start), Square::new(king_queenside_end), MoveFlags::QueenCastle));
+        }
     }
-    fn get_en_passant_moves(&self) {  
-        // Implement en passant move generation logic here
+    fn can_castle(&self, king_start: u8, rook_start: u8, king_end: u8) -> bool {
+        let king_start = king_start as usize;
+        let rook_start = rook_start as usize;
+        let king_end = king_end as usize;
+
+        // All of the squares between king and rook have to be empty
+        if BETWEEN_MASKS[king_start][rook_start] & self.combined_occupancy != 0 {
+            return false;
+        }
+        // But only the squares king stands on or passes thru have to be safe 
+        let king_path = (1 << king_start) | BETWEEN_MASKS[king_start][king_end] | (1 << king_end);
+        let opponent = self.game.side_to_move.opposite();
+        BitboardIter::new(king_path).all(|square| !self.game.board.is_square_attacked(square, opponent, self.combined_occupancy))
+    }
+    fn get_en_passant_moves(&self, moves: &mut MoveList) {
+        let Some(target) = self.game.en_passant else {
+            return;
+        };
+        let (pawns, enemy_pawns, pawn_rank, attacks_masks) = match self.game.side_to_move {
+            // Pawns that can capture on the square are the ones an opposite colored pawn from that square would attack
+            Side::White => (self.game.board.white_pieces[PieceType::Pawn as usize], self.game.board.black_pieces[PieceType::Pawn as usize], 4, &BLACK_PAWN_ATTACKS_MASKS),
+            Side::Black => (self.game.board.black_pieces[PieceType::Pawn as usize], self.game.board.white_pieces[PieceType::Pawn as usize], 3, &WHITE_PAWN_ATTACKS_MASKS),
+        };
+        // Pawn that has just made a double push stands right in front of the en passant square
+        let (file, _) = target.get_file_rank();
+        let captured = Square::new_from_file_rank(file, pawn_rank).unwrap();
+        if enemy_pawns & captured.get_mask() == 0 {
+            return;
+        }
+
+        for from in BitboardIter::new(attacks_masks[target.get_index()] & pawns) {
+            moves.push(Move::encode(from, target, MoveFlags::EpCapture));
+        }
     }
 
     // Basic moves generetes pseudo legal quiet and capture moves for pieces with attack masks.
     // For now, this function used only for knights and kings. In the case of the pawns they have different masks for attacks and captures,
     // Which creates the need for having separated functions for capture and quiet moves. I don't know yet if it will be possible to use them with sliding pieces, but for now that is the drill
     #[inline(always)]
-    fn get_basic_moves_for_pieces(&self, pieces_to_move: PieceType, attack_masks: &[Bitboard; 64]) -> Vec<Move> {
-        let mut moves = Vec::new(); 
-        moves.extend(self.get_capture_moves_for_pieces(pieces_to_move, attack_masks));
-        moves.extend(self.get_quiet_moves_for_pieces(pieces_to_move, attack_masks));  
-        
-        moves
+    fn get_basic_moves_for_pieces(&self, moves: &mut MoveList, pieces_to_move: PieceType, attack_masks: &[Bitboard; 64], capture_targets: Bitboard, quiet_targets: Bitboard) {
+        let pieces = self.game.board.get_pieces(self.game.side_to_move)[pieces_to_move as usize];
+
+        for from in BitboardIter::new(pieces) {
+            self.add_moves_to_targets(moves, from, attack_masks[from.get_index()], capture_targets, quiet_targets);
+        }
     }
+    // Sliding pieces attacks depend on the occupancy, so instead of static masks they are looked up for every piece
     #[inline(always)]
-    fn get_capture_moves_for_pieces(&self, pieces_to_move: PieceType, attack_masks: &[Bitboard; 64]) -> Vec<Move> {
-        match self.game.side_to_move {
-            Side::White => {        
-                let squares_with_pieces_to_move: Vec<Square> = Square::get_squares_from_bitboard(self.game.board.white_pieces[pieces_to_move as usize]);
-
-                squares_with_pieces_to_move.par_iter().flat_map(|&from| {
-                    let capture_moves_bitboard = (attack_masks[from.get_index()] & self.black_occupancy) & !self.white_occupancy;
-                    let capture_moves_squares = Square::get_squares_from_bitboard(capture_moves_bitboard);
-        
-                    capture_moves_squares.into_par_iter().map(move |capture| 
-                        Move::encode(from, capture, MoveFlags::Capture)
-                    )
-                }).collect()
-            },
-            Side::Black => {        
-                let squares_with_pieces_to_move: Vec<Square> = Square::get_squares_from_bitboard(self.game.board.black_pieces[pieces_to_move as usize]);
-
-                squares_with_pieces_to_move.par_iter().flat_map(|&from| {
-                    let capture_moves_bitboard = (attack_masks[from.get_index()] & self.white_occupancy) & !self.black_occupancy;
-                    let capture_moves_squares = Square::get_squares_from_bitboard(capture_moves_bitboard);
-        
-                    capture_moves_squares.into_par_iter().map(move |capture| 
-                        Move::encode(from, capture, MoveFlags::Capture)
-                    )
-                }).collect()
-            },
+    fn get_sliding_moves_for_pieces(&self, moves: &mut MoveList, pieces_to_move: PieceType, attacks: fn(Square, Bitboard) -> Bitboard, capture_targets: Bitboard, quiet_targets: Bitboard) {
+        let pieces = self.game.board.get_pieces(self.game.side_to_move)[pieces_to_move as usize];
+
+        for from in BitboardIter::new(pieces) {
+            self.add_moves_to_targets(moves, from, attacks(from, self.combined_occupancy), capture_targets, quiet_targets);
         }
     }
     #[inline(always)]
-    fn get_quiet_moves_for_pieces(&self, pieces_to_move: PieceType, attack_masks: &[Bitboard; 64]) -> Vec<Move>{
-        let squares_with_pieces_to_move: Vec<Square> = match self.game.side_to_move {
-            Side::White => Square::get_squares_from_bitboard(self.game.board.white_pieces[pieces_to_move as usize]),
-            Side::Black => Square::get_squares_from_bitboard(self.game.board.black_pieces[pieces_to_move as usize]),
-        };
-
-        squares_with_pieces_to_move.par_iter().flat_map(|&from| {
-            let quiet_moves_bitboard = attack_masks[from.get_index()] & !self.combined_occupancy;
-            let quiet_moves_squares = Square::get_squares_from_bitboard(quiet_moves_bitboard);
-            quiet_moves_squares.into_par_iter().map(move |quiet| 
-                Move::encode(from, quiet, MoveFlags::Quiet)
-            )
-        }).collect()
+    fn add_moves_to_targets(&self, moves: &mut MoveList, from: Square, attacks: Bitboard, capture_targets: Bitboard, quiet_targets: Bitboard) {
+        for capture in BitboardIter::new(attacks & capture_targets) {
+            moves.push(Move::encode(from, capture, MoveFlags::Capture));
+        }
+        for quiet in BitboardIter::new(attacks & quiet_targets) {
+            moves.push(Move::encode(from, quiet, MoveFlags::Quiet));
+        }
     }
-}
\ No newline at end of file
+}
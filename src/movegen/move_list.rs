@@ -0,0 +1,76 @@
+use crate::gamestate::Move;
+use std::ops::{Deref, DerefMut};
+
+// No legal chess position has more than 218 moves, so the list never has to grow
+pub const MAX_MOVES: usize = 256;
+
+// Fixed capacity list of moves that lives on the stack, so generating moves does not allocate.
+// It dereferences to a slice, which gives iteration, indexing, sorting and the rest of slice methods for free
+#[derive(Clone, Copy)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+impl MoveList {
+    #[inline(always)]
+    pub fn new() -> Self {
+        MoveList {
+            moves: [Move::NULL; MAX_MOVES],
+            len: 0,
+        }
+    }
+    #[inline(always)]
+    pub fn push(&mut self, mov: Move) {
+        debug_assert!(self.len < MAX_MOVES, "Move list is full");
+        self.moves[self.len] = mov;
+        self.len += 1;
+    }
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+    // Keeps only the moves the predicate returns true for, order of the kept moves does not change
+    #[inline(always)]
+    pub fn retain(&mut self, mut predicate: impl FnMut(&Move) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            if predicate(&self.moves[i]) {
+                self.moves[kept] = self.moves[i];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+}
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Deref for MoveList {
+    type Target = [Move];
+
+    #[inline(always)]
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+impl DerefMut for MoveList {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
+}
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+impl std::fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,81 @@
+use crate::gamestate::{
+    undo::UndoStack,
+    Gamestate,
+    Move,
+};
+use super::{MoveGen, MoveList};
+
+// Counts all the leaf nodes of the legal moves tree of the given depth, used to validate move generation.
+// Check https://www.chessprogramming.org/Perft for details and https://www.chessprogramming.org/Perft_Results for known results
+pub fn perft(game: &Gamestate, depth: usize) -> u64 {
+    let mut game = game.clone();
+    let mut undo_stack = UndoStack::new();
+    perft_recursive(&mut game, depth, &mut undo_stack)
+}
+
+// Same as perft, but node counts are given separately for every legal move in the root position,
+// which makes it possible to find the exact move where generation goes wrong
+pub fn divide(game: &Gamestate, depth: usize) -> Vec<(Move, u64)> {
+    assert!(depth > 0, "Divide needs at least one move to be made");
+
+    let mut game = game.clone();
+    let mut undo_stack = UndoStack::new();
+    let moves = MoveGen::new(&game).gererate();
+
+    moves.iter().map(|&mov| {
+        game.make_move_mut(&mov, &mut undo_stack);
+        let nodes = perft_recursive(&mut game, depth - 1, &mut undo_stack);
+        game.unmake_move(&mov, &mut undo_stack);
+        (mov, nodes)
+    }).collect()
+}
+
+// Same as perft, but moves come from the staged generators the search uses: evasions when in check,
+// captures followed by quiets otherwise. Matching perft counts mean the stages together give exactly the legal moves
+pub fn perft_staged(game: &Gamestate, depth: usize) -> u64 {
+    let mut game = game.clone();
+    let mut undo_stack = UndoStack::new();
+    perft_staged_recursive(&mut game, depth, &mut undo_stack)
+}
+
+fn perft_recursive(game: &mut Gamestate, depth: usize, undo_stack: &mut UndoStack) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = MoveGen::new(game).gererate();
+    // Moves are legal, so there is no need to make the last ones just to count them
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes: u64 = 0;
+    for mov in &moves {
+        game.make_move_mut(mov, undo_stack);
+        nodes += perft_recursive(game, depth - 1, undo_stack);
+        game.unmake_move(mov, undo_stack);
+    }
+    nodes
+}
+fn perft_staged_recursive(game: &mut Gamestate, depth: usize, undo_stack: &mut UndoStack) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let movegen = MoveGen::new(game);
+    let stages = if game.is_in_check() {
+        [movegen.generate_evasions(), MoveList::new()]
+    } else {
+        [movegen.generate_captures(), movegen.generate_quiets()]
+    };
+
+    let mut nodes: u64 = 0;
+    for mov in stages.iter().flat_map(|moves| moves.iter()) {
+        if depth == 1 {
+            nodes += 1;
+            continue;
+        }
+        game.make_move_mut(mov, undo_stack);
+        nodes += perft_staged_recursive(game, depth - 1, undo_stack);
+        game.unmake_move(mov, undo_stack);
+    }
+    nodes
+}
@@ -0,0 +1,69 @@
+use crate::gamestate::board::*;
+use super::magic_bitboards::{attacks_on_the_fly, occupancy_subsets, relevant_occupancy_mask};
+
+use core::arch::x86_64::_pext_u64;
+use lazy_static::lazy_static;
+
+// Check https://www.chessprogramming.org/BMI2#PEXTBitboards for details.
+// PEXT packs the relevant occupancy bits into a dense index, so no magic numbers are needed,
+// the tables have the same layout as the fancy magic ones.
+lazy_static! {
+    static ref PEXT_TABLES: PextTables = init_pext_tables();
+}
+
+#[derive(Default, Copy, Clone)]
+struct PextEntry {
+    mask: Bitboard,
+    offset: usize,
+}
+
+struct PextTables {
+    rook_entries: [PextEntry; 64],
+    bishop_entries: [PextEntry; 64],
+    attacks: Vec<Bitboard>,
+}
+
+/// # Safety
+/// CPU has to support BMI2, check it with `is_x86_feature_detected!("bmi2")` first.
+#[target_feature(enable = "bmi2")]
+pub unsafe fn rook_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    let tables: &PextTables = &PEXT_TABLES;
+    let entry = tables.rook_entries[square.get_index()];
+    tables.attacks[entry.offset + _pext_u64(occupancy, entry.mask) as usize]
+}
+/// # Safety
+/// CPU has to support BMI2, check it with `is_x86_feature_detected!("bmi2")` first.
+#[target_feature(enable = "bmi2")]
+pub unsafe fn bishop_attacks(square: Square, occupancy: Bitboard) -> Bitboard {
+    let tables: &PextTables = &PEXT_TABLES;
+    let entry = tables.bishop_entries[square.get_index()];
+    tables.attacks[entry.offset + _pext_u64(occupancy, entry.mask) as usize]
+}
+
+// Filling the table does not use PEXT itself, so it is fine to run it on any CPU
+fn init_pext_tables() -> PextTables {
+    let mut attacks: Vec<Bitboard> = Vec::new();
+
+    let rook_entries = init_entries_for_piece(PieceType::Rook, &mut attacks);
+    let bishop_entries = init_entries_for_piece(PieceType::Bishop, &mut attacks);
+
+    PextTables {
+        rook_entries,
+        bishop_entries,
+        attacks,
+    }
+}
+fn init_entries_for_piece(piece: PieceType, attacks: &mut Vec<Bitboard>) -> [PextEntry; 64] {
+    let mut entries = [PextEntry::default(); 64];
+
+    for (i, entry) in entries.iter_mut().enumerate() {
+        let square = Square::new(i as u8);
+        let mask = relevant_occupancy_mask(piece, square);
+        *entry = PextEntry { mask, offset: attacks.len() };
+
+        // Carry-Rippler enumerates subsets in the increasing order of their packed bits,
+        // so n-th subset is exactly the one PEXT maps to index n
+        attacks.extend(occupancy_subsets(mask).into_iter().map(|occupancy| attacks_on_the_fly(piece, square, occupancy)));
+    }
+    entries
+}
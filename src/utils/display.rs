@@ -5,6 +5,7 @@ use crate::gamestate::{
         CastlingSide,
     },
     defs::*,
+    status::GameResult,
     Gamestate,
     Move,
     MoveFlags
@@ -31,18 +32,16 @@ pub const EMPTY: char = '.';
 
 impl Display for Gamestate {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // Print board
-        writeln!(f, "a b c d e f g h")?;
-        for rank in (0..BOARD_SIDE_LENGTH).rev() {
-            for file in 0..BOARD_SIDE_LENGTH {
-                let square: Square;
-
-                // Depending on the current player to move it will "turn" the board
-                // using reversed indexing
-                match self.side_to_move {
-                    Side::White => {square = Square::new_from_file_rank(7 - file as u8, 7 - rank as u8).unwrap()},
-                    Side::Black => {square = Square::new_from_file_rank(file as u8, rank as u8).unwrap()},
-                }
+        // Print board, depending on the current player to move it will "turn" the board,
+        // so the side to move is always at the bottom
+        let (header, ranks, files): (&str, Vec<usize>, Vec<usize>) = match self.side_to_move {
+            Side::White => ("a b c d e f g h", (0..BOARD_SIDE_LENGTH).rev().collect(), (0..BOARD_SIDE_LENGTH).collect()),
+            Side::Black => ("h g f e d c b a", (0..BOARD_SIDE_LENGTH).collect(), (0..BOARD_SIDE_LENGTH).rev().collect()),
+        };
+        writeln!(f, "{}", header)?;
+        for &rank in &ranks {
+            for &file in &files {
+                let square = Square::new_from_file_rank(file as u8, rank as u8).unwrap();
 
                 let piece_char = match self.board.get_piece_at_square(square) {
                     None => EMPTY,
@@ -153,4 +152,16 @@ impl fmt::Display for MoveFlags {
         };
         write!(f, "{}", s)
     }
-}
\ No newline at end of file
+}
+
+// Same notation as the result tag in PGN
+impl Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        };
+        write!(f, "{}", s)
+    }
+}
@@ -3,6 +3,7 @@ use crate::gamestate::{
     castling_rights::{CastlingRights, CastlingSide},
     Gamestate,
     defs::*,
+    validation::PositionError,
 };
 use core::str;
 use thiserror::Error;
@@ -11,6 +12,10 @@ pub const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk
 
 // This constants used to parse fen string
 const FEN_NR_OF_PARTS: usize = 6;
+// EPD has no clocks, lenient parsing takes them as default when they are missing
+const EPD_NR_OF_PARTS: usize = 4;
+const DEFAULT_HALF_MOVE_CLOCK: u8 = 0;
+const DEFAULT_FULL_MOVE_COUNT: u8 = 1;
 const SPLITTER: char = '/';
 const DASH: char = '-';
 const SPACE: char = ' ';
@@ -58,16 +63,33 @@ pub enum FenError {
     PieceLayout(String),
     #[error("Error in 2 part of FEN:  Colors")]
     StartingSide,
-    #[error("Error in 3 part of FEN:  Castling rights")]
-    CastlingRights,
+    #[error("Error in 3 part of FEN:  Castling rights: `{0}`")]
+    CastlingRights(String),
     #[error("Error in 4 part of FEN:  En passant field")]
     EnPassant,
     #[error("Error in 5 part of FEN: : Half-move clock")]
     HalfMoveClock,
     #[error("Error in 6 part of FEN:  Full-move number")]
     FullMoveCounte,
+
+    #[error("FEN describes an invalid position: {0}")]
+    InvalidPosition(#[from] PositionError),
+
+    #[error("FEN string has only `{0}` elements, expected at least 4")]
+    NotEnoughElements(usize),
+    #[error("Unexpected element `{0}` after the full-move number")]
+    UnexpectedElement(String),
+    // Returned by lenient parsing, field index and character offset in the whole string are both counted from 0
+    #[error("{error} (field {field}, character {offset})")]
+    Located {
+        field: usize,
+        offset: usize,
+        error: Box<FenError>,
+    },
 }
 pub type FenResult = Result<Gamestate, FenError>;
+// Error together with the character offset of the problem inside the field it was found in
+type FieldResult<T> = Result<T, (usize, FenError)>;
 
 // Fen struct is used to independently implement fen logic,
 // instead of having it be a part of Gamestate
@@ -79,47 +101,133 @@ impl Fen {
             return Err(FenError::InvalidNumOfElements(separated_fen.len()));
         }
 
-        let board = Self::get_board(separated_fen[BOARD_LAYOUT])?;
-        let side_to_move = Self::get_side_to_move(separated_fen[STARTING_SIDE])?;
-        let castling_rights = Self::get_castling_rights(separated_fen[CASTLING_RIGHTS])?;
-        let en_passant = Self::get_en_passant(separated_fen[EN_PASSANT])?;
-        let half_move_clock = Self::get_half_move_clock(separated_fen[HALF_MOVE_CLOCK])?;
-        let full_move_count = Self::get_full_move_count(separated_fen[FULL_MOVE_COUNTER])?;
+        Self::parse_fields(&separated_fen).map_err(|(_, _, error)| error)
+    }
+    // Accepts what other tools tend to emit: any amount of whitespace between fields, missing clocks (as in EPD)
+    // and EPD operations after the position, which are ignored. Errors carry the place of the problem in the string
+    pub fn process_lenient(&self) -> FenResult {
+        let fields = Self::split_fields_with_offsets(&self.0);
+        let end_offset = self.0.chars().count();
+        if fields.len() < EPD_NR_OF_PARTS {
+            return Err(FenError::Located {
+                field: fields.len(),
+                offset: end_offset,
+                error: Box::new(FenError::NotEnoughElements(fields.len())),
+            });
+        }
+
+        // EPD operations start with an opcode, so the first field beginning with a letter ends the position
+        let operations_start = fields.iter()
+            .skip(EPD_NR_OF_PARTS)
+            .position(|(_, field)| field.starts_with(|ch: char| ch.is_ascii_alphabetic()))
+            .map_or(fields.len(), |position| position + EPD_NR_OF_PARTS);
+        if operations_start > FEN_NR_OF_PARTS {
+            let (offset, field) = fields[FEN_NR_OF_PARTS];
+            return Err(FenError::Located {
+                field: FEN_NR_OF_PARTS,
+                offset,
+                error: Box::new(FenError::UnexpectedElement(field.to_string())),
+            });
+        }
 
-        return Ok(Gamestate::new(board, side_to_move, castling_rights, en_passant, half_move_clock, full_move_count))
+        let separated_fen: Vec<&str> = fields[..operations_start].iter().map(|&(_, field)| field).collect();
+        Self::parse_fields(&separated_fen).map_err(|(field, offset, error)| FenError::Located {
+            field,
+            offset: fields[field].0 + offset,
+            error: Box::new(error),
+        })
     }
+    // Writes the position back to FEN, ranks go from the 8th to the 1st and files from a to h
+    pub fn from_gamestate(game: &Gamestate) -> Fen {
+        let fields = [
+            Self::board_to_fen(&game.board),
+            Self::side_to_move_to_fen(game.side_to_move),
+            Self::castling_rights_to_fen(game.castling_rights),
+            Self::en_passant_to_fen(game.en_passant),
+            game.half_move_clock.to_string(),
+            game.full_move_count.to_string(),
+        ];
+        Fen(fields.join(&SPACE.to_string()))
+    }
+    // Same as process, but the position also has to pass Gamestate::validate, so it is safe to generate moves for
+    pub fn process_strict(&self) -> FenResult {
+        let game = self.process()?;
+        game.validate()?;
+        Ok(game)
+    }
+
+    // Takes 4 to 6 fields, missing clocks get their default values.
+    // On failure gives the index of the field and the character offset of the problem inside it
+    fn parse_fields(fields: &[&str]) -> Result<Gamestate, (usize, usize, FenError)> {
+        let at_start = |field: usize| move |error: FenError| (field, 0, error);
+        let at_offset = |field: usize| move |(offset, error): (usize, FenError)| (field, offset, error);
 
-    fn get_board(s: &str) -> Result<Board, FenError> {
+        let board = Self::get_board(fields[BOARD_LAYOUT]).map_err(at_offset(BOARD_LAYOUT))?;
+        let side_to_move = Self::get_side_to_move(fields[STARTING_SIDE]).map_err(at_start(STARTING_SIDE))?;
+        let castling_rights = Self::get_castling_rights(fields[CASTLING_RIGHTS]).map_err(at_offset(CASTLING_RIGHTS))?;
+        let en_passant = Self::get_en_passant(fields[EN_PASSANT]).map_err(at_start(EN_PASSANT))?;
+        let half_move_clock = match fields.get(HALF_MOVE_CLOCK) {
+            Some(field) => Self::get_half_move_clock(field).map_err(at_start(HALF_MOVE_CLOCK))?,
+            None => DEFAULT_HALF_MOVE_CLOCK,
+        };
+        let full_move_count = match fields.get(FULL_MOVE_COUNTER) {
+            Some(field) => Self::get_full_move_count(field).map_err(at_start(FULL_MOVE_COUNTER))?,
+            None => DEFAULT_FULL_MOVE_COUNT,
+        };
+
+        Ok(Gamestate::new(board, side_to_move, castling_rights, en_passant, half_move_clock, full_move_count))
+    }
+    // Splits on any whitespace, every field comes with the character offset it starts at
+    fn split_fields_with_offsets(s: &str) -> Vec<(usize, &str)> {
+        let mut fields = vec![];
+        let mut field_start: Option<(usize, usize)> = None; // Character offset and byte index
+        for (offset, (byte_index, ch)) in s.char_indices().enumerate() {
+            match (ch.is_whitespace(), field_start) {
+                (true, Some((start_offset, start_byte))) => {
+                    fields.push((start_offset, &s[start_byte..byte_index]));
+                    field_start = None;
+                },
+                (false, None) => field_start = Some((offset, byte_index)),
+                _ => (),
+            }
+        }
+        if let Some((start_offset, start_byte)) = field_start {
+            fields.push((start_offset, &s[start_byte..]));
+        }
+        fields
+    }
+
+    fn get_board(s: &str) -> FieldResult<Board> {
         let fen_ranks: Vec<&str> = s.split(SPLITTER).collect();
         if fen_ranks.len() != 8 {
-            return Err(FenError::PieceLayout(format!("The number of board ranks is not equal to 8, ranks number = `{}`", fen_ranks.len())));
+            return Err((0, FenError::PieceLayout(format!("The number of board ranks is not equal to 8, ranks number = `{}`", fen_ranks.len()))));
         }
     
         let mut board = Board::default();
+        // Character offset of the current rank inside the field
+        let mut rank_start = 0;
         for (rank_index, rank) in fen_ranks.iter().enumerate() {
-            if rank_index >= BOARD_SIDE_LENGTH as usize {
-                return Err(FenError::PieceLayout(format!("Rank index value is more than 8, rank index = `{}`", rank_index)));
-            }
-    
             let mut file_index: u8 = 0;
-            for piece in rank.chars() {
-                if piece.is_digit(10) {
+            for (char_index, piece) in rank.chars().enumerate() {
+                let offset = rank_start + char_index;
+                if piece.is_ascii_digit() {
                     let empty_squares = piece.to_digit(10).unwrap() as u8;
                     if !(1..=8).contains(&empty_squares) {
-                        return Err(FenError::PieceLayout(format!("Invalid number of empty squares: {}, at rank {}, file index {}", empty_squares, rank_index + 1, file_index + 1)));
+                        return Err((offset, FenError::PieceLayout(format!("Invalid number of empty squares: {}, at rank {}, file index {}", empty_squares, rank_index + 1, file_index + 1))));
                     }
     
                     file_index += empty_squares;
+                    if file_index > BOARD_SIDE_LENGTH as u8 {
+                        return Err((offset, FenError::PieceLayout(format!("Empty squares go past the h file, file index = `{}`", file_index))));
+                    }
                     continue;
                 }
                 if file_index >= BOARD_SIDE_LENGTH as u8 {
-                    return Err(FenError::PieceLayout(format!("File index value is more than 8, file index = `{}`", file_index)));
+                    return Err((offset, FenError::PieceLayout(format!("File index value is more than 8, file index = `{}`", file_index))));
                 }
     
-                let square = match Square::new_from_file_rank(7 - file_index, 7 - rank_index as u8) {
-                    Some(s) => s,
-                    None => return Err(FenError::PieceLayout("Invalid file or rank had been passed".to_string())),
-                };
+                // FEN starts from the 8th rank and goes from the a to the h file
+                let square = Square::new_from_file_rank(file_index, 7 - rank_index as u8).unwrap();
     
                 match piece {
                     WHITE_KING =>   board.place_piece_at_square(square, PieceType::King, Side::White),
@@ -136,15 +244,19 @@ impl Fen {
                     BLACK_KNIGHT => board.place_piece_at_square(square, PieceType::Knight, Side::Black),
                     BLACK_PAWN =>   board.place_piece_at_square(square, PieceType::Pawn, Side::Black),
     
-                    _ => return Err(FenError::PieceLayout(format!("Invalid symbol '{}' encountered", piece))),
+                    _ => return Err((offset, FenError::PieceLayout(format!("Invalid symbol '{}' encountered", piece)))),
                 }
                 // Increment index
                 file_index += 1;
             }
     
+            let rank_length = rank.chars().count();
             if file_index != BOARD_SIDE_LENGTH as u8 {
-                return Err(FenError::PieceLayout(format!("By the end of the rank, file does not have exactly 8 squares, but {}", file_index)));
+                // Points at the end of the rank, since the squares are counted up to there
+                return Err((rank_start + rank_length, FenError::PieceLayout(format!("By the end of the rank, file does not have exactly 8 squares, but {}", file_index))));
             }
+            // Next rank starts after the separator
+            rank_start += rank_length + 1;
         }
     
         Ok(board)
@@ -160,37 +272,44 @@ impl Fen {
             _ => Err(FenError::StartingSide),
         }
     }
-    fn get_castling_rights(s: &str) -> Result<CastlingRights, FenError> {
-        if s.len() != 4 {
-            return Err(FenError::CastlingRights);
+    // Castling field is either `-` or any of `KQkq` in any order, each of them at most once
+    fn get_castling_rights(s: &str) -> FieldResult<CastlingRights> {
+        if s == DASH.to_string() {
+            return Ok(CastlingRights::new());
+        }
+        if s.is_empty() {
+            return Err((0, FenError::CastlingRights("Field is empty, use `-` for no castling rights".to_string())));
         }
 
         let mut cr = CastlingRights::new();
-        for ch in s.chars() {
-            match ch {
-                WHITE_KINGSIDE  => cr.set_for_side(Side::White, CastlingSide::Kingside),
-                WHITE_QUEENSIDE => cr.set_for_side(Side::White, CastlingSide::Queenside),
-                BLACK_KINGSIDE  => cr.set_for_side(Side::Black, CastlingSide::Kingside),
-                BLACK_QUEENSIDE => cr.set_for_side(Side::Black, CastlingSide::Queenside),
-                _ => return Err(FenError::CastlingRights),
+        for (offset, ch) in s.chars().enumerate() {
+            let (side, castling_side) = match ch {
+                WHITE_KINGSIDE  => (Side::White, CastlingSide::Kingside),
+                WHITE_QUEENSIDE => (Side::White, CastlingSide::Queenside),
+                BLACK_KINGSIDE  => (Side::Black, CastlingSide::Kingside),
+                BLACK_QUEENSIDE => (Side::Black, CastlingSide::Queenside),
+                DASH => return Err((offset, FenError::CastlingRights(format!("`{}` can not be combined with castling rights in `{}`", DASH, s)))),
+                _ => return Err((offset, FenError::CastlingRights(format!("Invalid symbol '{}' encountered", ch)))),
+            };
+            let current = cr.get_for_side(side);
+            if (castling_side.has_kingside() && current.has_kingside()) || (castling_side.has_queenside() && current.has_queenside()) {
+                return Err((offset, FenError::CastlingRights(format!("Symbol '{}' is repeated in `{}`", ch, s))));
             }
+            cr.set_for_side(side, castling_side);
         }
         Ok(cr)
     }
-    fn get_en_passant(s: &str) -> Result<u8, FenError> {
-        if s == "-" {
-            return Ok(0 as u8);
+    // En passant square can only be on the 3rd or the 6th rank, right behind the pawn that has just made a double push
+    fn get_en_passant(s: &str) -> Result<Option<Square>, FenError> {
+        if s == DASH.to_string() {
+            return Ok(None);
         }
         if s.len() != 2 {
             return Err(FenError::EnPassant);
         }
         match Square::new_from_algebraic_notation(s) {
-            Some(sq) => {
-                let (_, rank) = sq.get_file_rank();
-                let en_passant = 1 << rank;
-                Ok(en_passant)
-            }
-            None => Err(FenError::EnPassant),
+            Some(sq) if matches!(sq.get_file_rank().1, 2 | 5) => Ok(Some(sq)),
+            _ => Err(FenError::EnPassant),
         }
     }
     fn get_half_move_clock(s: &str) -> Result<u8, FenError> {
@@ -205,4 +324,81 @@ impl Fen {
             Err(_) => Err(FenError::FullMoveCounte),
         }
     }
+
+    fn board_to_fen(board: &Board) -> String {
+        let mut s = String::new();
+        for rank in (0..BOARD_SIDE_LENGTH as u8).rev() {
+            let mut empty_squares = 0;
+            for file in 0..BOARD_SIDE_LENGTH as u8 {
+                let square = Square::new_from_file_rank(file, rank).unwrap();
+                let piece = match board.get_piece_at_square(square) {
+                    Some(piece) => piece,
+                    None => {
+                        empty_squares += 1;
+                        continue;
+                    },
+                };
+                if empty_squares > 0 {
+                    s.push_str(&empty_squares.to_string());
+                    empty_squares = 0;
+                }
+                s.push(match piece {
+                    (PieceType::King, Side::White) =>   WHITE_KING,
+                    (PieceType::Queen, Side::White) =>  WHITE_QUEEN,
+                    (PieceType::Rook, Side::White) =>   WHITE_ROOK,
+                    (PieceType::Bishop, Side::White) => WHITE_BISHOP,
+                    (PieceType::Knight, Side::White) => WHITE_KNIGHT,
+                    (PieceType::Pawn, Side::White) =>   WHITE_PAWN,
+
+                    (PieceType::King, Side::Black) =>   BLACK_KING,
+                    (PieceType::Queen, Side::Black) =>  BLACK_QUEEN,
+                    (PieceType::Rook, Side::Black) =>   BLACK_ROOK,
+                    (PieceType::Bishop, Side::Black) => BLACK_BISHOP,
+                    (PieceType::Knight, Side::Black) => BLACK_KNIGHT,
+                    (PieceType::Pawn, Side::Black) =>   BLACK_PAWN,
+                });
+            }
+            if empty_squares > 0 {
+                s.push_str(&empty_squares.to_string());
+            }
+            if rank > 0 {
+                s.push(SPLITTER);
+            }
+        }
+        s
+    }
+    fn side_to_move_to_fen(side: Side) -> String {
+        match side {
+            Side::White => WHITE_SIDE.to_string(),
+            Side::Black => BLACK_SIDE.to_string(),
+        }
+    }
+    fn castling_rights_to_fen(castling_rights: CastlingRights) -> String {
+        let white = castling_rights.get_for_side(Side::White);
+        let black = castling_rights.get_for_side(Side::Black);
+        let s: String = [
+            (white.has_kingside(), WHITE_KINGSIDE),
+            (white.has_queenside(), WHITE_QUEENSIDE),
+            (black.has_kingside(), BLACK_KINGSIDE),
+            (black.has_queenside(), BLACK_QUEENSIDE),
+        ].iter().filter(|(allowed, _)| *allowed).map(|&(_, ch)| ch).collect();
+
+        if s.is_empty() {
+            DASH.to_string()
+        } else {
+            s
+        }
+    }
+    fn en_passant_to_fen(en_passant: Option<Square>) -> String {
+        match en_passant {
+            Some(square) => square.to_algebraic_notation(),
+            None => DASH.to_string(),
+        }
+    }
+}
+
+impl Gamestate {
+    pub fn to_fen(&self) -> String {
+        Fen::from_gamestate(self).0
+    }
 }
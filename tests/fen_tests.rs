@@ -0,0 +1,284 @@
+#[cfg(test)]
+mod tests {
+    use rusty_chess_engine::gamestate::{board::*, castling_rights::*, Gamestate, Move, MoveFlags};
+    use rusty_chess_engine::utils::fen::{Fen, FenError, DEFAULT_FEN};
+    use rusty_chess_engine::movegen::MoveGen;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn square(s: &str) -> Square {
+        Square::new_from_algebraic_notation(s).unwrap()
+    }
+    fn mov(from: &str, to: &str, flags: MoveFlags) -> Move {
+        Move::encode(square(from), square(to), flags)
+    }
+    fn all_castling_rights() -> CastlingRights {
+        let mut rights = CastlingRights::new();
+        rights.set_for_side(Side::White, CastlingSide::Both);
+        rights.set_for_side(Side::Black, CastlingSide::Both);
+        rights
+    }
+    // Built from pieces, so the writer is checked independently from the parser
+    fn start_position() -> Gamestate {
+        let back_rank = [
+            PieceType::Rook, PieceType::Knight, PieceType::Bishop, PieceType::Queen,
+            PieceType::King, PieceType::Bishop, PieceType::Knight, PieceType::Rook,
+        ];
+        let mut board = Board::default();
+        for (file, piece_type) in back_rank.into_iter().enumerate() {
+            let file = file as u8;
+            board.place_piece_at_square(Square::new_from_file_rank(file, 0).unwrap(), piece_type, Side::White);
+            board.place_piece_at_square(Square::new_from_file_rank(file, 1).unwrap(), PieceType::Pawn, Side::White);
+            board.place_piece_at_square(Square::new_from_file_rank(file, 6).unwrap(), PieceType::Pawn, Side::Black);
+            board.place_piece_at_square(Square::new_from_file_rank(file, 7).unwrap(), piece_type, Side::Black);
+        }
+        Gamestate::new(board, Side::White, all_castling_rights(), None, 0, 1)
+    }
+
+    #[test]
+    fn test_to_fen_start_position() {
+        assert_eq!(start_position().to_fen(), DEFAULT_FEN);
+    }
+
+    #[test]
+    fn test_to_fen_after_moves() {
+        let game = start_position().make_move(&mov("e2", "e4", MoveFlags::DoublePawnPush));
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+
+        let game = game.make_move(&mov("c7", "c5", MoveFlags::DoublePawnPush));
+        assert_eq!(game.to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2");
+
+        let game = game.make_move(&mov("g1", "f3", MoveFlags::Quiet));
+        assert_eq!(game.to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+    }
+
+    #[test]
+    fn test_to_fen_castling_rights() {
+        let mut board = Board::default();
+        board.place_piece_at_square(square("e1"), PieceType::King, Side::White);
+        board.place_piece_at_square(square("h1"), PieceType::Rook, Side::White);
+        board.place_piece_at_square(square("e8"), PieceType::King, Side::Black);
+        board.place_piece_at_square(square("a8"), PieceType::Rook, Side::Black);
+
+        let mut rights = CastlingRights::new();
+        rights.set_for_side(Side::White, CastlingSide::Kingside);
+        rights.set_for_side(Side::Black, CastlingSide::Queenside);
+        let game = Gamestate::new(board.clone(), Side::White, rights, None, 12, 40);
+        assert_eq!(game.to_fen(), "r3k3/8/8/8/8/8/8/4K2R w Kq - 12 40");
+
+        let game = Gamestate::new(board, Side::Black, CastlingRights::new(), None, 0, 1);
+        assert_eq!(game.to_fen(), "r3k3/8/8/8/8/8/8/4K2R b - - 0 1");
+    }
+
+    // Plays random games and parses every position written on the way, which has to give the same gamestate back
+    #[test]
+    fn test_fen_round_trip_random_games() {
+        const GAMES: usize = 50;
+        const MAX_PLIES: usize = 200;
+        let mut rng = StdRng::seed_from_u64(21);
+
+        for _ in 0..GAMES {
+            let mut game = start_position();
+            for _ in 0..MAX_PLIES {
+                let fen = game.to_fen();
+                let parsed = Fen(fen.clone()).process().unwrap_or_else(|e| panic!("{} failed to parse: {}", fen, e));
+                assert_eq!(parsed, game, "round trip changed the position for {}", fen);
+
+                let moves = MoveGen::new(&game).gererate();
+                if moves.is_empty() {
+                    break;
+                }
+                game = game.make_move(&moves[rng.gen_range(0..moves.len())]);
+            }
+        }
+    }
+
+    fn castling_of(fen: &str) -> (CastlingSide, CastlingSide) {
+        let game = Fen(fen.to_string()).process().unwrap_or_else(|e| panic!("{} failed to parse: {}", fen, e));
+        (game.castling_rights.get_for_side(Side::White), game.castling_rights.get_for_side(Side::Black))
+    }
+
+    #[test]
+    fn test_parse_fen_corpus() {
+        // Standard perft positions, common opening positions and endgames, together with their castling rights
+        let corpus = [
+            (DEFAULT_FEN, CastlingSide::Both, CastlingSide::Both),
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", CastlingSide::Both, CastlingSide::Both),
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", CastlingSide::Both, CastlingSide::Both),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", CastlingSide::None, CastlingSide::None),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", CastlingSide::None, CastlingSide::Both),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", CastlingSide::Both, CastlingSide::None),
+            ("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", CastlingSide::None, CastlingSide::None),
+            ("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4", CastlingSide::None, CastlingSide::Both),
+            ("rnbqkb1r/pp2pppp/3p1n2/8/3NP3/8/PPP2PPP/RNBQKB1R w KQkq - 1 5", CastlingSide::Both, CastlingSide::Both),
+            ("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1", CastlingSide::Kingside, CastlingSide::Queenside),
+            ("r3k2r/8/8/8/8/8/8/R3K2R b Qk - 0 1", CastlingSide::Queenside, CastlingSide::Kingside),
+            ("8/8/4k3/8/8/4K3/8/8 w - - 0 1", CastlingSide::None, CastlingSide::None),
+            ("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 32", CastlingSide::None, CastlingSide::None),
+        ];
+        for (fen, white, black) in corpus {
+            assert_eq!(castling_of(fen), (white, black), "wrong castling rights for {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_parse_castling_rights_any_order() {
+        let rights = (CastlingSide::Both, CastlingSide::Both);
+        for field in ["KQkq", "qkQK", "kKqQ", "QqKk"] {
+            assert_eq!(castling_of(&format!("r3k2r/8/8/8/8/8/8/R3K2R w {} - 0 1", field)), rights);
+        }
+        assert_eq!(castling_of("r3k2r/8/8/8/8/8/8/R3K2R w qK - 0 1"), (CastlingSide::Kingside, CastlingSide::Queenside));
+        assert_eq!(castling_of("r3k2r/8/8/8/8/8/8/R3K2R w k - 0 1"), (CastlingSide::None, CastlingSide::Kingside));
+    }
+
+    #[test]
+    fn test_parse_invalid_castling_rights() {
+        for field in ["KK", "KQkqq", "K-", "-K", "--", "KX", "kqa"] {
+            let fen = format!("r3k2r/8/8/8/8/8/8/R3K2R w {} - 0 1", field);
+            assert!(matches!(Fen(fen.clone()).process(), Err(FenError::CastlingRights(_))), "{} should not parse", fen);
+        }
+        // Empty field is caught as a missing element, since FEN is split on spaces
+        let empty_field = Fen("r3k2r/8/8/8/8/8/8/R3K2R w  - 0 1".to_string()).process();
+        assert!(empty_field.is_err());
+    }
+
+    fn en_passant_of(field: &str) -> Result<Option<Square>, FenError> {
+        Fen(format!("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq {} 0 1", field)).process().map(|game| game.en_passant)
+    }
+
+    #[test]
+    fn test_parse_en_passant() {
+        assert_eq!(en_passant_of("e3").unwrap(), Some(square("e3")));
+        assert_eq!(en_passant_of("h6").unwrap(), Some(square("h6")));
+        assert_eq!(en_passant_of("-").unwrap(), None);
+        for field in ["e4", "a1", "e9", "i3", "e", "e33", "3e"] {
+            assert!(matches!(en_passant_of(field), Err(FenError::EnPassant)), "{} should not parse", field);
+        }
+
+        // Written square is read back as is
+        let game = start_position().make_move(&mov("d2", "d4", MoveFlags::DoublePawnPush));
+        assert_eq!(Fen(game.to_fen()).process().unwrap().en_passant, Some(square("d3")));
+    }
+
+    fn piece_at(game: &Gamestate, s: &str) -> Option<(PieceType, Side)> {
+        game.board.get_piece_at_square(square(s))
+    }
+
+    #[test]
+    fn test_parse_piece_placement() {
+        let game = Fen(DEFAULT_FEN.to_string()).process().unwrap();
+        assert_eq!(game, start_position());
+        assert_eq!(piece_at(&game, "a1"), Some((PieceType::Rook, Side::White)));
+        assert_eq!(piece_at(&game, "d1"), Some((PieceType::Queen, Side::White)));
+        assert_eq!(piece_at(&game, "e1"), Some((PieceType::King, Side::White)));
+        assert_eq!(piece_at(&game, "e2"), Some((PieceType::Pawn, Side::White)));
+        assert_eq!(piece_at(&game, "d8"), Some((PieceType::Queen, Side::Black)));
+        assert_eq!(piece_at(&game, "h8"), Some((PieceType::Rook, Side::Black)));
+        assert_eq!(piece_at(&game, "e4"), None);
+
+        let kiwipete = Fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string()).process().unwrap();
+        assert_eq!(piece_at(&kiwipete, "e5"), Some((PieceType::Knight, Side::White)));
+        assert_eq!(piece_at(&kiwipete, "f3"), Some((PieceType::Queen, Side::White)));
+        assert_eq!(piece_at(&kiwipete, "d5"), Some((PieceType::Pawn, Side::White)));
+        assert_eq!(piece_at(&kiwipete, "a6"), Some((PieceType::Bishop, Side::Black)));
+        assert_eq!(piece_at(&kiwipete, "e7"), Some((PieceType::Queen, Side::Black)));
+        assert_eq!(piece_at(&kiwipete, "h3"), Some((PieceType::Pawn, Side::Black)));
+        assert_eq!(piece_at(&kiwipete, "b4"), Some((PieceType::Pawn, Side::Black)));
+        assert_eq!(kiwipete.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_parsed_e2e4_moves_the_e_pawn() {
+        let game = Fen(DEFAULT_FEN.to_string()).process().unwrap();
+        let e2e4 = MoveGen::new(&game).gererate().iter()
+            .find(|m| m.get_from_square() == square("e2") && m.get_to_square() == square("e4"))
+            .copied()
+            .unwrap();
+        let game = game.make_move(&e2e4);
+        assert_eq!(piece_at(&game, "e4"), Some((PieceType::Pawn, Side::White)));
+        assert_eq!(piece_at(&game, "e2"), None);
+        assert_eq!(piece_at(&game, "d2"), Some((PieceType::Pawn, Side::White)));
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    }
+
+    #[test]
+    fn test_display_orientation() {
+        let game = Fen(DEFAULT_FEN.to_string()).process().unwrap();
+        let shown = game.to_string();
+        let lines: Vec<&str> = shown.lines().collect();
+        assert_eq!(lines[0], "a b c d e f g h");
+        assert_eq!(lines[1], "♜ ♞ ♝ ♛ ♚ ♝ ♞ ♜ 8");
+        assert_eq!(lines[8], "♖ ♘ ♗ ♕ ♔ ♗ ♘ ♖ 1");
+
+        // Black sees the board from the other side
+        let game = game.make_move(&mov("e2", "e4", MoveFlags::DoublePawnPush));
+        let shown = game.to_string();
+        let lines: Vec<&str> = shown.lines().collect();
+        assert_eq!(lines[0], "h g f e d c b a");
+        assert_eq!(lines[1], "♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖ 1");
+        assert_eq!(lines[4], ". . . ♙ . . . . 4");
+        assert_eq!(lines[8], "♜ ♞ ♝ ♚ ♛ ♝ ♞ ♜ 8");
+    }
+
+    fn lenient(fen: &str) -> Result<Gamestate, FenError> {
+        Fen(fen.to_string()).process_lenient()
+    }
+
+    // Field index and character offset of a lenient parsing error
+    fn error_location(fen: &str) -> (usize, usize) {
+        match lenient(fen) {
+            Err(FenError::Located { field, offset, .. }) => (field, offset),
+            other => panic!("expected a located error for `{}`, got {:?}", fen, other),
+        }
+    }
+
+    #[test]
+    fn test_lenient_defaults_missing_clocks() {
+        let game = lenient("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap();
+        assert_eq!(game.to_fen(), DEFAULT_FEN);
+
+        let game = lenient("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 7").unwrap();
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 7 1");
+
+        // Strict parsing still wants all six fields
+        assert!(Fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -".to_string()).process().is_err());
+    }
+
+    #[test]
+    fn test_lenient_tolerates_whitespace() {
+        let game = lenient("  rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR   w\tKQkq  -  0 \t 1 \n").unwrap();
+        assert_eq!(game.to_fen(), DEFAULT_FEN);
+        assert!(Fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR  w KQkq - 0 1".to_string()).process().is_err());
+    }
+
+    #[test]
+    fn test_lenient_ignores_epd_operations() {
+        let game = lenient("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - bm e5f7; id \"kiwipete\";").unwrap();
+        assert_eq!(game.to_fen(), "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+
+        let game = lenient("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3 12 id \"start\";").unwrap();
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3 12");
+    }
+
+    #[test]
+    fn test_lenient_error_positions() {
+        // Invalid piece symbol, offset counts from the start of the string
+        assert_eq!(error_location("rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), (0, 13));
+        // Rank with too few squares points at its end
+        assert_eq!(error_location("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), (0, 16));
+        assert_eq!(error_location("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR  x KQkq - 0 1"), (1, 45));
+        assert_eq!(error_location("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQxq - 0 1"), (2, 48));
+        assert_eq!(error_location("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e5 0 1"), (3, 51));
+        assert_eq!(error_location("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1x"), (5, 55));
+        // Empty squares past the h file point at the digit that overflows the rank
+        assert_eq!(error_location("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN8 w KQkq - 0 1"), (0, 42));
+        let overflowing_rank = "8".repeat(33);
+        assert_eq!(error_location(&format!("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/{} w - - 0 1", overflowing_rank)), (0, 36));
+        assert!(Fen(format!("{}/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1", overflowing_rank)).process().is_err());
+        // Missing fields are reported at the end of the string, extra ones where they start
+        assert_eq!(error_location("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq"), (3, 50));
+        assert_eq!(error_location("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 2"), (6, 57));
+
+        let error = lenient("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQxq - 0 1").unwrap_err();
+        assert_eq!(error.to_string(), "Error in 3 part of FEN:  Castling rights: `Invalid symbol 'x' encountered` (field 2, character 48)");
+    }
+}
@@ -1,6 +1,50 @@
 #[cfg(test)]
 mod tests {
-    use rusty_chess_engine::gamestate::{board::*, castling_rights::*};
+    use rusty_chess_engine::gamestate::{board::*, castling_rights::*, history::History, polyglot::*, status::*, undo::UndoStack, validation::PositionError, zobrist::Zobrist, Gamestate, Move, MoveFlags};
+    use rusty_chess_engine::movegen::MoveGen;
+    use rusty_chess_engine::utils::fen::{Fen, FenError, DEFAULT_FEN};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Builds a position from a list of pieces given in algebraic notation
+    fn game_from_pieces(pieces: &[(&str, PieceType, Side)], side_to_move: Side, castling_rights: CastlingRights) -> Gamestate {
+        let mut board = Board::default();
+        for &(square, piece_type, side) in pieces {
+            board.place_piece_at_square(Square::new_from_algebraic_notation(square).unwrap(), piece_type, side);
+        }
+        Gamestate::new(board, side_to_move, castling_rights, None, 0, 1)
+    }
+    fn mov(from: &str, to: &str, flags: MoveFlags) -> Move {
+        Move::encode(
+            Square::new_from_algebraic_notation(from).unwrap(),
+            Square::new_from_algebraic_notation(to).unwrap(),
+            flags,
+        )
+    }
+    fn square(s: &str) -> Square {
+        Square::new_from_algebraic_notation(s).unwrap()
+    }
+    fn piece_at(game: &Gamestate, square: &str) -> Option<(PieceType, Side)> {
+        game.board.get_piece_at_square(Square::new_from_algebraic_notation(square).unwrap())
+    }
+    fn all_castling_rights() -> CastlingRights {
+        let mut rights = CastlingRights::new();
+        rights.set_for_side(Side::White, CastlingSide::Both);
+        rights.set_for_side(Side::Black, CastlingSide::Both);
+        rights
+    }
+    fn start_position() -> Gamestate {
+        Fen(DEFAULT_FEN.to_string()).process().unwrap()
+    }
+    fn castling_position() -> Gamestate {
+        game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("a1", PieceType::Rook, Side::White),
+            ("h1", PieceType::Rook, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("a8", PieceType::Rook, Side::Black),
+            ("h8", PieceType::Rook, Side::Black),
+        ], Side::White, all_castling_rights())
+    }
 
     #[test]
     fn test_square_new_from_file_rank() {
@@ -60,6 +104,14 @@ mod tests {
         assert_eq!(squares, expected_squares);
     }
 
+    #[test]
+    fn test_bitboard_iter() {
+        let iter = BitboardIter::new(0b10101010 | (1 << 63));
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.collect::<Vec<Square>>(), vec![Square::new(1), Square::new(3), Square::new(5), Square::new(7), Square::new(63)]);
+        assert_eq!(BitboardIter::new(0).next(), None);
+    }
+
     #[test]
     fn test_board_place_piece_at_square() {
         let mut board = Board::default();
@@ -149,6 +201,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_attackers_to() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("c3", PieceType::Pawn, Side::White),
+            ("f6", PieceType::Knight, Side::White),
+            ("d8", PieceType::Rook, Side::White),
+            ("h8", PieceType::King, Side::Black),
+            ("e5", PieceType::Pawn, Side::Black),
+            ("a7", PieceType::Bishop, Side::Black),
+            ("d1", PieceType::Queen, Side::Black),
+        ], Side::White, CastlingRights::new());
+        let square = |coords: &str| Square::new_from_algebraic_notation(coords).unwrap();
+        let bitboard = |squares: &[&str]| squares.iter().fold(0, |acc, coords| acc | square(coords).get_mask());
+
+        // Pawns, knights, sliders and kings of both sides
+        assert_eq!(game.attackers_to(square("d4")), bitboard(&["c3", "e5", "a7", "d8", "d1"]));
+        assert_eq!(game.board.attackers_of_side(square("d4"), Side::White, game.board.get_combined_occupancy()), bitboard(&["c3", "d8"]));
+        assert_eq!(game.attackers_to(square("e2")), bitboard(&["e1", "d1"]));
+        assert!(game.is_square_attacked(square("e4"), Side::White));
+        assert!(!game.is_square_attacked(square("e4"), Side::Black));
+
+        // Removing the blocker from the occupancy reveals the rook behind it
+        let occupancy = game.board.get_combined_occupancy() & !square("d1").get_mask();
+        assert_eq!(game.board.attackers_of_side(square("d1"), Side::White, occupancy), bitboard(&["e1", "d8"]));
+    }
+
+    #[test]
+    fn test_checkers() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("a4", PieceType::Queen, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("d5", PieceType::Pawn, Side::Black),
+        ], Side::White, CastlingRights::new());
+        assert!(!game.is_in_check());
+
+        let mut undo_stack = UndoStack::new();
+        let mut in_place = game.clone();
+        let check = mov("a4", "b5", MoveFlags::Quiet);
+        in_place.make_move_mut(&check, &mut undo_stack);
+        assert!(in_place.is_in_check());
+        assert_eq!(in_place.checkers, Square::new_from_algebraic_notation("b5").unwrap().get_mask());
+        assert_eq!(in_place, game.make_move(&check));
+
+        in_place.unmake_move(&check, &mut undo_stack);
+        assert!(!in_place.is_in_check());
+    }
+
     #[test]
     fn test_castling_rights_new() {
         let rights = CastlingRights::new();
@@ -168,6 +269,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_for_side() {
+        let mut rights = CastlingRights::new();
+        rights.set_for_side(Side::White, CastlingSide::Kingside);
+        rights.set_for_side(Side::Black, CastlingSide::Queenside);
+        assert_eq!(rights.get_for_side(Side::White), CastlingSide::Kingside);
+        assert_eq!(rights.get_for_side(Side::Black), CastlingSide::Queenside);
+
+        rights.disable_all();
+        rights.set_for_side(Side::Black, CastlingSide::Both);
+        assert_eq!(rights.get_for_side(Side::White), CastlingSide::None);
+        assert_eq!(rights.get_for_side(Side::Black), CastlingSide::Both);
+    }
+
     #[test]
     fn test_disable_full_side() {
         let mut rights = CastlingRights::new();
@@ -209,4 +324,674 @@ mod tests {
         rights.disable_all();
         assert_eq!(rights.get(), 0);
     }
+
+    #[test]
+    fn test_make_move_quiet() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("g1", PieceType::Knight, Side::White),
+            ("e8", PieceType::King, Side::Black),
+        ], Side::White, CastlingRights::new());
+
+        let new_game = game.make_move(&mov("g1", "f3", MoveFlags::Quiet));
+        assert_eq!(piece_at(&new_game, "g1"), None);
+        assert_eq!(piece_at(&new_game, "f3"), Some((PieceType::Knight, Side::White)));
+        assert_eq!(new_game.side_to_move, Side::Black);
+        assert_eq!(new_game.half_move_clock, 1);
+        assert_eq!(new_game.full_move_count, 1);
+        assert_eq!(new_game.zobrist_key, Zobrist::hash(&new_game));
+        // Copy-make leaves the original untouched
+        assert_eq!(piece_at(&game, "g1"), Some((PieceType::Knight, Side::White)));
+    }
+
+    #[test]
+    fn test_make_move_double_pawn_push_and_en_passant() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e2", PieceType::Pawn, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("d4", PieceType::Pawn, Side::Black),
+        ], Side::White, CastlingRights::new());
+
+        let after_push = game.make_move(&mov("e2", "e4", MoveFlags::DoublePawnPush));
+        assert_eq!(after_push.en_passant, Some(square("e3")));
+        assert_eq!(after_push.half_move_clock, 0);
+        assert_eq!(after_push.zobrist_key, Zobrist::hash(&after_push));
+
+        let after_capture = after_push.make_move(&mov("d4", "e3", MoveFlags::EpCapture));
+        assert_eq!(piece_at(&after_capture, "e4"), None);
+        assert_eq!(piece_at(&after_capture, "d4"), None);
+        assert_eq!(piece_at(&after_capture, "e3"), Some((PieceType::Pawn, Side::Black)));
+        assert_eq!(after_capture.en_passant, None);
+        assert_eq!(after_capture.full_move_count, 2);
+        assert_eq!(after_capture.zobrist_key, Zobrist::hash(&after_capture));
+    }
+
+    #[test]
+    fn test_make_move_capture_resets_half_move_clock() {
+        let mut game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("d1", PieceType::Queen, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("d7", PieceType::Knight, Side::Black),
+        ], Side::White, CastlingRights::new());
+        game.half_move_clock = 12;
+
+        let new_game = game.make_move(&mov("d1", "d7", MoveFlags::Capture));
+        assert_eq!(piece_at(&new_game, "d7"), Some((PieceType::Queen, Side::White)));
+        assert_eq!(new_game.board.black_pieces[PieceType::Knight as usize], 0);
+        assert_eq!(new_game.half_move_clock, 0);
+        assert_eq!(new_game.zobrist_key, Zobrist::hash(&new_game));
+    }
+
+    #[test]
+    fn test_make_move_castling() {
+        let game = castling_position();
+
+        let white_kingside = game.make_move(&mov("e1", "g1", MoveFlags::KingCastle));
+        assert_eq!(piece_at(&white_kingside, "g1"), Some((PieceType::King, Side::White)));
+        assert_eq!(piece_at(&white_kingside, "f1"), Some((PieceType::Rook, Side::White)));
+        assert_eq!(piece_at(&white_kingside, "h1"), None);
+        assert_eq!(white_kingside.castling_rights.get_for_side(Side::White), CastlingSide::None);
+        assert_eq!(white_kingside.zobrist_key, Zobrist::hash(&white_kingside));
+
+        let black_queenside = white_kingside.make_move(&mov("e8", "c8", MoveFlags::QueenCastle));
+        assert_eq!(piece_at(&black_queenside, "c8"), Some((PieceType::King, Side::Black)));
+        assert_eq!(piece_at(&black_queenside, "d8"), Some((PieceType::Rook, Side::Black)));
+        assert_eq!(piece_at(&black_queenside, "a8"), None);
+        assert_eq!(black_queenside.castling_rights.get(), 0);
+        assert_eq!(black_queenside.zobrist_key, Zobrist::hash(&black_queenside));
+    }
+
+    #[test]
+    fn test_make_move_castling_rights_loss() {
+        let game = castling_position();
+
+        // Rook leaving its home square
+        let rook_moved = game.make_move(&mov("h1", "h4", MoveFlags::Quiet));
+        assert_eq!(rook_moved.castling_rights.get(),
+            (CastlingSide::Queenside as u8) | ((CastlingSide::Both as u8) << BLACK_SIDE_OFFSET));
+
+        // Rook being captured on its home square
+        let rook_captured = game.make_move(&mov("a1", "a8", MoveFlags::Capture));
+        assert_eq!(rook_captured.castling_rights.get(),
+            (CastlingSide::Kingside as u8) | ((CastlingSide::Kingside as u8) << BLACK_SIDE_OFFSET));
+        assert_eq!(rook_captured.zobrist_key, Zobrist::hash(&rook_captured));
+
+        // King moving without castling
+        let king_moved = game.make_move(&mov("e1", "e2", MoveFlags::Quiet));
+        assert_eq!(king_moved.castling_rights.get(), (CastlingSide::Both as u8) << BLACK_SIDE_OFFSET);
+        assert_eq!(king_moved.zobrist_key, Zobrist::hash(&king_moved));
+    }
+
+    #[test]
+    fn test_make_move_promotions() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("b7", PieceType::Pawn, Side::White),
+            ("a8", PieceType::Rook, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+        ], Side::White, CastlingRights::new());
+
+        let promotions = [
+            (MoveFlags::KnightPromotion, PieceType::Knight),
+            (MoveFlags::BishopPromotion, PieceType::Bishop),
+            (MoveFlags::RookPromotion, PieceType::Rook),
+            (MoveFlags::QueenPromotion, PieceType::Queen),
+        ];
+        for (flags, piece_type) in promotions {
+            let new_game = game.make_move(&mov("b7", "b8", flags));
+            assert_eq!(piece_at(&new_game, "b8"), Some((piece_type, Side::White)));
+            assert_eq!(new_game.board.white_pieces[PieceType::Pawn as usize], 0);
+            assert_eq!(new_game.zobrist_key, Zobrist::hash(&new_game));
+        }
+
+        let promo_captures = [
+            (MoveFlags::KnightPromoCapture, PieceType::Knight),
+            (MoveFlags::BishopPromoCapture, PieceType::Bishop),
+            (MoveFlags::RookPromoCapture, PieceType::Rook),
+            (MoveFlags::QueenPromoCapture, PieceType::Queen),
+        ];
+        for (flags, piece_type) in promo_captures {
+            let new_game = game.make_move(&mov("b7", "a8", flags));
+            assert_eq!(piece_at(&new_game, "a8"), Some((piece_type, Side::White)));
+            assert_eq!(new_game.board.black_pieces[PieceType::Rook as usize], 0);
+            assert_eq!(new_game.board.white_pieces[PieceType::Pawn as usize], 0);
+            assert_eq!(new_game.zobrist_key, Zobrist::hash(&new_game));
+        }
+    }
+
+    // Every move made in place has to give the same position as copy-make, and unmaking it has to give back the original
+    fn assert_make_unmake_equivalent(game: &Gamestate, moves: &[Move]) {
+        let mut undo_stack = UndoStack::new();
+        let mut in_place = game.clone();
+        for m in moves {
+            let copied = in_place.make_move(m);
+            let before = in_place.clone();
+
+            in_place.make_move_mut(m, &mut undo_stack);
+            assert_eq!(in_place, copied, "make_move_mut differs from make_move for {}", m);
+
+            in_place.unmake_move(m, &mut undo_stack);
+            assert_eq!(in_place, before, "unmake_move did not restore the position for {}", m);
+            assert!(undo_stack.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_make_unmake_single_moves() {
+        assert_make_unmake_equivalent(&castling_position(), &[
+            mov("e1", "g1", MoveFlags::KingCastle),
+            mov("e1", "c1", MoveFlags::QueenCastle),
+            mov("h1", "h8", MoveFlags::Capture),
+            mov("a1", "a5", MoveFlags::Quiet),
+            mov("e1", "d2", MoveFlags::Quiet),
+        ]);
+
+        let promotion_position = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("b7", PieceType::Pawn, Side::White),
+            ("a8", PieceType::Rook, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+        ], Side::White, CastlingRights::new());
+        assert_make_unmake_equivalent(&promotion_position, &[
+            mov("b7", "b8", MoveFlags::KnightPromotion),
+            mov("b7", "b8", MoveFlags::BishopPromotion),
+            mov("b7", "b8", MoveFlags::RookPromotion),
+            mov("b7", "b8", MoveFlags::QueenPromotion),
+            mov("b7", "a8", MoveFlags::KnightPromoCapture),
+            mov("b7", "a8", MoveFlags::BishopPromoCapture),
+            mov("b7", "a8", MoveFlags::RookPromoCapture),
+            mov("b7", "a8", MoveFlags::QueenPromoCapture),
+        ]);
+
+        // Move counter saturates, unmake still has to give the original value back
+        let capped_counter = Fen("4k3/8/8/8/8/8/8/4K3 b - - 0 255".to_string()).process().unwrap();
+        assert_make_unmake_equivalent(&capped_counter, &[mov("e8", "d8", MoveFlags::Quiet)]);
+    }
+
+    #[test]
+    fn test_make_unmake_sequence() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e2", PieceType::Pawn, Side::White),
+            ("h1", PieceType::Rook, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("d4", PieceType::Pawn, Side::Black),
+            ("a8", PieceType::Rook, Side::Black),
+        ], Side::White, all_castling_rights());
+        let moves = [
+            mov("e2", "e4", MoveFlags::DoublePawnPush),
+            mov("d4", "e3", MoveFlags::EpCapture),
+            mov("e1", "g1", MoveFlags::KingCastle),
+            mov("e8", "c8", MoveFlags::QueenCastle),
+            mov("f1", "f8", MoveFlags::Quiet),
+            mov("d8", "f8", MoveFlags::Capture),
+        ];
+
+        let mut undo_stack = UndoStack::new();
+        let mut in_place = game.clone();
+        let mut copied = vec![game.clone()];
+        for m in &moves {
+            let next = copied.last().unwrap().make_move(m);
+            copied.push(next);
+            in_place.make_move_mut(m, &mut undo_stack);
+            assert_eq!(&in_place, copied.last().unwrap());
+        }
+        assert_eq!(undo_stack.len(), moves.len());
+
+        for m in moves.iter().rev() {
+            copied.pop();
+            in_place.unmake_move(m, &mut undo_stack);
+            assert_eq!(&in_place, copied.last().unwrap());
+        }
+        assert_eq!(in_place, game);
+    }
+
+    // Plays random legal games and checks the incremental key against the one computed from scratch after every move,
+    // both for copy-make and make-unmake. Also checks that every move type was played at least once
+    #[test]
+    fn test_incremental_zobrist_random_games() {
+        const GAMES: usize = 200;
+        const MAX_PLIES: usize = 400;
+
+        let mut rng = StdRng::seed_from_u64(15);
+        let mut played_flags = [false; 16];
+        // Black double pushes land next to the white pawns, so en passant captures come up often
+        let en_passant_position = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("b5", PieceType::Pawn, Side::White),
+            ("d5", PieceType::Pawn, Side::White),
+            ("f5", PieceType::Pawn, Side::White),
+            ("h5", PieceType::Pawn, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("a7", PieceType::Pawn, Side::Black),
+            ("c7", PieceType::Pawn, Side::Black),
+            ("e7", PieceType::Pawn, Side::Black),
+            ("g7", PieceType::Pawn, Side::Black),
+        ], Side::Black, CastlingRights::new());
+        let start_positions = [start_position(), castling_position(), en_passant_position];
+
+        for game_index in 0..GAMES {
+            let mut game = start_positions[game_index % start_positions.len()].clone();
+            let mut undo_stack = UndoStack::new();
+            let mut played = vec![];
+            let mut keys = vec![game.zobrist_key];
+
+            for _ in 0..MAX_PLIES {
+                let moves = MoveGen::new(&game).gererate();
+                if moves.is_empty() {
+                    break;
+                }
+                let m = moves[rng.gen_range(0..moves.len())];
+                played_flags[m.get_flags() as usize] = true;
+
+                let copied = game.make_move(&m);
+                game.make_move_mut(&m, &mut undo_stack);
+                assert_eq!(game.zobrist_key, Zobrist::hash(&game), "incremental key differs after {}", m);
+                assert_eq!(copied.zobrist_key, game.zobrist_key, "copy-make key differs after {}", m);
+                assert_eq!(game.pawn_key, Zobrist::pawn_hash(&game), "incremental pawn key differs after {}", m);
+                assert_eq!(game.material_key, Zobrist::material_hash(&game), "incremental material key differs after {}", m);
+                played.push(m);
+                keys.push(game.zobrist_key);
+            }
+
+            // Unmaking restores every key on the way back
+            while let Some(m) = played.pop() {
+                keys.pop();
+                game.unmake_move(&m, &mut undo_stack);
+                assert_eq!(game.zobrist_key, *keys.last().unwrap());
+                assert_eq!(game.zobrist_key, Zobrist::hash(&game));
+                assert_eq!(game.pawn_key, Zobrist::pawn_hash(&game));
+                assert_eq!(game.material_key, Zobrist::material_hash(&game));
+            }
+        }
+
+        for flags in (0..16).filter(|&f| f != 6 && f != 7) {
+            assert!(played_flags[flags], "move type {:?} was never played", MoveFlags::from_u8(flags as u8));
+        }
+    }
+
+    #[test]
+    fn test_pawn_and_material_keys() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("b1", PieceType::Knight, Side::White),
+            ("e4", PieceType::Pawn, Side::White),
+            ("b7", PieceType::Pawn, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("d5", PieceType::Pawn, Side::Black),
+            ("a8", PieceType::Rook, Side::Black),
+        ], Side::White, CastlingRights::new());
+
+        // Piece moves keep the pawn key, no move without capture or promotion changes material
+        let knight_moved = game.make_move(&mov("b1", "c3", MoveFlags::Quiet));
+        assert_eq!(knight_moved.pawn_key, game.pawn_key);
+        assert_eq!(knight_moved.material_key, game.material_key);
+        let king_moved = game.make_move(&mov("e1", "e2", MoveFlags::Quiet));
+        assert_ne!(king_moved.pawn_key, game.pawn_key);
+        assert_eq!(king_moved.material_key, game.material_key);
+
+        // Capture changes both, and material is the same as of a position with the same pieces on other squares
+        let pawn_captured = game.make_move(&mov("e4", "d5", MoveFlags::Capture));
+        assert_ne!(pawn_captured.pawn_key, game.pawn_key);
+        let same_material = game_from_pieces(&[
+            ("g1", PieceType::King, Side::White),
+            ("f3", PieceType::Knight, Side::White),
+            ("a2", PieceType::Pawn, Side::White),
+            ("h2", PieceType::Pawn, Side::White),
+            ("g8", PieceType::King, Side::Black),
+            ("h8", PieceType::Rook, Side::Black),
+        ], Side::Black, CastlingRights::new());
+        assert_eq!(pawn_captured.material_key, same_material.material_key);
+
+        // Promotion capture takes away the rook and turns the pawn into a queen
+        let promoted = game.make_move(&mov("b7", "a8", MoveFlags::QueenPromoCapture));
+        let promoted_material = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("b1", PieceType::Knight, Side::White),
+            ("e4", PieceType::Pawn, Side::White),
+            ("d1", PieceType::Queen, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("d5", PieceType::Pawn, Side::Black),
+        ], Side::Black, CastlingRights::new());
+        assert_eq!(promoted.material_key, promoted_material.material_key);
+        assert_eq!(promoted.pawn_key, Zobrist::pawn_hash(&promoted));
+    }
+
+    #[test]
+    fn test_material_key_with_many_pieces_of_type() {
+        let game = Fen("QQQQQQQQ/QQQQQQQQ/QQQ5/8/8/8/8/K6k w - - 0 1".to_string()).process().unwrap();
+        assert_eq!(game.material_key, Zobrist::material_hash(&game));
+        assert_ne!(game.material_key, Zobrist::material_hash(&Fen("QQQQQQQQ/QQQQQQQQ/QQ6/8/8/8/8/K6k w - - 0 1".to_string()).process().unwrap()));
+    }
+
+    // Any table of distinct values is enough to check which entries get hashed
+    fn polyglot_test_table() -> [u64; POLYGLOT_KEYS_NUM] {
+        let mut rng = StdRng::seed_from_u64(16);
+        let mut random64 = [0u64; POLYGLOT_KEYS_NUM];
+        random64.iter_mut().for_each(|value| *value = rng.gen());
+        random64
+    }
+
+    #[test]
+    fn test_polyglot_key_layout() {
+        let random64 = polyglot_test_table();
+        let keys = PolyglotKeys::new(random64);
+
+        // Piece kinds go black pawn, white pawn, ... black king (10), white king (11), square index is LERF
+        let kings_only = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e8", PieceType::King, Side::Black),
+        ], Side::White, CastlingRights::new());
+        let kings_key = random64[64 * 11 + 4] ^ random64[64 * 10 + 60];
+        assert_eq!(kings_only.polyglot_key(&keys), kings_key ^ random64[780]);
+
+        let mut black_to_move = kings_only.clone();
+        black_to_move.side_to_move = Side::Black;
+        assert_eq!(black_to_move.polyglot_key(&keys), kings_key);
+
+        // White rook is kind 7, castling entries go white short, white long, black short, black long
+        let mut rights = CastlingRights::new();
+        rights.set_for_side(Side::White, CastlingSide::Kingside);
+        rights.set_for_side(Side::Black, CastlingSide::Queenside);
+        let with_castling = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("h1", PieceType::Rook, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("a8", PieceType::Rook, Side::Black),
+        ], Side::White, rights);
+        let expected = kings_key ^ random64[64 * 7 + 7] ^ random64[64 * 6 + 56]
+            ^ random64[768] ^ random64[771] ^ random64[780];
+        assert_eq!(with_castling.polyglot_key(&keys), expected);
+    }
+
+    #[test]
+    fn test_polyglot_key_en_passant_only_when_capturable() {
+        let random64 = polyglot_test_table();
+        let keys = PolyglotKeys::new(random64);
+        let double_push = mov("e2", "e4", MoveFlags::DoublePawnPush);
+
+        // No black pawn can take on e3, so the file is not hashed even though our own key includes it
+        let lone_pawn = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e2", PieceType::Pawn, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("a4", PieceType::Pawn, Side::Black),
+        ], Side::White, CastlingRights::new()).make_move(&double_push);
+        let mut without_en_passant = lone_pawn.clone();
+        without_en_passant.en_passant = None;
+        assert_eq!(lone_pawn.polyglot_key(&keys), without_en_passant.polyglot_key(&keys));
+
+        // With a black pawn next to it the capture is possible, and the e file gets hashed
+        let capturable = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e2", PieceType::Pawn, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("d4", PieceType::Pawn, Side::Black),
+        ], Side::White, CastlingRights::new()).make_move(&double_push);
+        let mut without_en_passant = capturable.clone();
+        without_en_passant.en_passant = None;
+        assert_ne!(capturable.polyglot_key(&keys), without_en_passant.polyglot_key(&keys));
+
+        assert_eq!(capturable.polyglot_key(&keys), without_en_passant.polyglot_key(&keys) ^ random64[772 + 4]);
+    }
+
+    fn knights_position() -> Gamestate {
+        game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("g1", PieceType::Knight, Side::White),
+            ("a2", PieceType::Pawn, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("g8", PieceType::Knight, Side::Black),
+        ], Side::White, CastlingRights::new())
+    }
+    fn knights_shuffle() -> [Move; 4] {
+        [
+            mov("g1", "f3", MoveFlags::Quiet),
+            mov("g8", "f6", MoveFlags::Quiet),
+            mov("f3", "g1", MoveFlags::Quiet),
+            mov("f6", "g8", MoveFlags::Quiet),
+        ]
+    }
+
+    #[test]
+    fn test_history_repetition() {
+        let mut game = knights_position();
+        let mut history = History::new();
+        history.push(&game);
+        assert!(!history.is_repetition(2));
+
+        for m in knights_shuffle() {
+            game = game.make_move(&m);
+            history.push(&game);
+        }
+        assert!(history.is_repetition(2));
+        assert!(!history.is_repetition(3));
+
+        for m in knights_shuffle() {
+            game = game.make_move(&m);
+            history.push(&game);
+        }
+        assert!(history.is_repetition(3));
+        assert_eq!(history.len(), 9);
+        assert_eq!(history.get_last(), Some(game.zobrist_key));
+
+        // Pawn move is irreversible, so the shuffle after it starts counting from scratch
+        for m in [mov("a2", "a3", MoveFlags::Quiet), mov("e8", "d8", MoveFlags::Quiet), mov("e1", "d1", MoveFlags::Quiet)] {
+            game = game.make_move(&m);
+            history.push(&game);
+        }
+        assert!(!history.is_repetition(2));
+        for m in [mov("d8", "e8", MoveFlags::Quiet), mov("d1", "e1", MoveFlags::Quiet), mov("e8", "d8", MoveFlags::Quiet)] {
+            game = game.make_move(&m);
+            history.push(&game);
+        }
+        assert!(history.is_repetition(2));
+        assert!(!history.is_repetition(3));
+    }
+
+    #[test]
+    fn test_history_with_make_unmake() {
+        let mut game = knights_position();
+        let mut history = History::new();
+        let mut undo_stack = UndoStack::new();
+        history.push(&game);
+
+        // Search pushes the position after every move and pops it after unmaking
+        let moves = knights_shuffle();
+        for m in &moves {
+            game.make_move_mut(m, &mut undo_stack);
+            history.push(&game);
+        }
+        assert!(history.is_repetition(2));
+
+        for m in moves.iter().rev() {
+            game.unmake_move(m, &mut undo_stack);
+            history.pop();
+            assert_eq!(history.get_last(), Some(game.zobrist_key));
+        }
+        assert_eq!(history.len(), 1);
+        assert!(!history.is_repetition(2));
+    }
+
+    fn status_of(game: &Gamestate) -> GameStatus {
+        let mut history = History::new();
+        history.push(game);
+        game.status(&history)
+    }
+
+    #[test]
+    fn test_status_checkmate_and_stalemate() {
+        let back_rank_mate = game_from_pieces(&[
+            ("g1", PieceType::King, Side::White),
+            ("f2", PieceType::Pawn, Side::White),
+            ("g2", PieceType::Pawn, Side::White),
+            ("h2", PieceType::Pawn, Side::White),
+            ("a1", PieceType::Rook, Side::Black),
+            ("g8", PieceType::King, Side::Black),
+        ], Side::White, CastlingRights::new());
+        assert_eq!(status_of(&back_rank_mate), GameStatus::Checkmate(Side::Black));
+        assert_eq!(status_of(&back_rank_mate).result(), Some(GameResult::BlackWins));
+
+        // Mate given with the last move allowed by the move rules still wins
+        let mut mate_on_clock = back_rank_mate.clone();
+        mate_on_clock.half_move_clock = 150;
+        assert_eq!(status_of(&mate_on_clock), GameStatus::Checkmate(Side::Black));
+
+        let stalemate = game_from_pieces(&[
+            ("h1", PieceType::King, Side::White),
+            ("g3", PieceType::Queen, Side::Black),
+            ("f2", PieceType::King, Side::Black),
+        ], Side::White, CastlingRights::new());
+        assert_eq!(status_of(&stalemate), GameStatus::Stalemate);
+        assert_eq!(status_of(&stalemate).result(), Some(GameResult::Draw));
+
+        assert_eq!(status_of(&start_position()), GameStatus::Ongoing);
+        assert_eq!(status_of(&start_position()).result(), None);
+        assert!(!status_of(&start_position()).is_game_over());
+    }
+
+    #[test]
+    fn test_status_move_rules() {
+        let mut game = castling_position();
+        game.half_move_clock = 99;
+        assert_eq!(status_of(&game), GameStatus::Ongoing);
+        game.half_move_clock = 100;
+        assert_eq!(status_of(&game), GameStatus::FiftyMoveRule);
+        assert!(status_of(&game).is_claimable_draw());
+        game.half_move_clock = 150;
+        assert_eq!(status_of(&game), GameStatus::SeventyFiveMoveRule);
+        assert!(!status_of(&game).is_claimable_draw());
+    }
+
+    #[test]
+    fn test_status_repetitions() {
+        let mut game = knights_position();
+        let mut history = History::new();
+        history.push(&game);
+
+        let mut statuses = vec![];
+        for _ in 0..4 {
+            for m in knights_shuffle() {
+                game = game.make_move(&m);
+                history.push(&game);
+            }
+            statuses.push(game.status(&history));
+        }
+        assert_eq!(statuses, [
+            GameStatus::Ongoing,
+            GameStatus::ThreefoldRepetition,
+            GameStatus::ThreefoldRepetition,
+            GameStatus::FivefoldRepetition,
+        ]);
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let kings = [("e1", PieceType::King, Side::White), ("e8", PieceType::King, Side::Black)];
+        let with = |extra: &[(&str, PieceType, Side)]| {
+            let pieces: Vec<_> = kings.iter().chain(extra.iter()).copied().collect();
+            game_from_pieces(&pieces, Side::White, CastlingRights::new())
+        };
+
+        assert!(with(&[]).has_insufficient_material());
+        assert!(with(&[("c1", PieceType::Bishop, Side::White)]).has_insufficient_material());
+        assert!(with(&[("b8", PieceType::Knight, Side::Black)]).has_insufficient_material());
+        // c1 and f8 are both dark squares
+        assert!(with(&[("c1", PieceType::Bishop, Side::White), ("f8", PieceType::Bishop, Side::Black)]).has_insufficient_material());
+        assert_eq!(status_of(&with(&[])), GameStatus::InsufficientMaterial);
+
+        // c1 is dark and c8 is light
+        assert!(!with(&[("c1", PieceType::Bishop, Side::White), ("c8", PieceType::Bishop, Side::Black)]).has_insufficient_material());
+        assert!(!with(&[("c1", PieceType::Bishop, Side::White), ("b1", PieceType::Knight, Side::White)]).has_insufficient_material());
+        assert!(!with(&[("b1", PieceType::Knight, Side::White), ("g1", PieceType::Knight, Side::White)]).has_insufficient_material());
+        assert!(!with(&[("a2", PieceType::Pawn, Side::White)]).has_insufficient_material());
+        assert!(!with(&[("a1", PieceType::Rook, Side::White)]).has_insufficient_material());
+    }
+
+    #[test]
+    fn test_game_result_display() {
+        assert_eq!(GameResult::WhiteWins.to_string(), "1-0");
+        assert_eq!(GameResult::BlackWins.to_string(), "0-1");
+        assert_eq!(GameResult::Draw.to_string(), "1/2-1/2");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(start_position().validate(), Ok(()));
+        assert_eq!(castling_position().validate(), Ok(()));
+
+        let no_black_king = game_from_pieces(&[("e1", PieceType::King, Side::White)], Side::White, CastlingRights::new());
+        assert_eq!(no_black_king.validate(), Err(PositionError::InvalidKingCount(Side::Black, 0)));
+        let two_white_kings = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("a1", PieceType::King, Side::White),
+            ("e8", PieceType::King, Side::Black),
+        ], Side::White, CastlingRights::new());
+        assert_eq!(two_white_kings.validate(), Err(PositionError::InvalidKingCount(Side::White, 2)));
+
+        let pawn_on_back_rank = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("h8", PieceType::Pawn, Side::White),
+        ], Side::White, CastlingRights::new());
+        assert_eq!(pawn_on_back_rank.validate(), Err(PositionError::PawnOnBackRank(Square::new_from_algebraic_notation("h8").unwrap())));
+
+        // Rights stay after the rook or the king are moved away by hand
+        let mut rook_moved = castling_position();
+        rook_moved.board.clear_square(Square::new_from_algebraic_notation("h1").unwrap());
+        assert_eq!(rook_moved.validate(), Err(PositionError::InvalidCastlingRights(Side::White, CastlingSide::Kingside)));
+        let mut king_moved = castling_position();
+        king_moved.board.clear_square(Square::new_from_algebraic_notation("e8").unwrap());
+        king_moved.board.place_piece_at_square(Square::new_from_algebraic_notation("d8").unwrap(), PieceType::King, Side::Black);
+        assert_eq!(king_moved.validate(), Err(PositionError::InvalidCastlingRights(Side::Black, CastlingSide::Kingside)));
+
+        let opponent_in_check = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e2", PieceType::Rook, Side::White),
+            ("e8", PieceType::King, Side::Black),
+        ], Side::White, CastlingRights::new());
+        assert_eq!(opponent_in_check.validate(), Err(PositionError::OpponentInCheck(Side::Black)));
+
+        // Placing a piece does not clear the square, so the board ends up with a knight and a pawn on e4
+        let mut overlapping = castling_position();
+        overlapping.board.place_piece_at_square(square("e4"), PieceType::Knight, Side::White);
+        overlapping.board.place_piece_at_square(square("e4"), PieceType::Pawn, Side::Black);
+        assert_eq!(overlapping.validate(), Err(PositionError::OverlappingPieces(square("e4"))));
+    }
+
+    #[test]
+    fn test_validate_en_passant() {
+        let after_double_push = start_position().make_move(&mov("e2", "e4", MoveFlags::DoublePawnPush));
+        assert_eq!(after_double_push.validate(), Ok(()));
+
+        // No pawn stands on d4 after a push, and the square behind e4 is e3, not e2
+        let mut wrong_file = after_double_push.clone();
+        wrong_file.en_passant = Some(square("d3"));
+        assert_eq!(wrong_file.validate(), Err(PositionError::InvalidEnPassant));
+        let mut wrong_rank = after_double_push.clone();
+        wrong_rank.en_passant = Some(square("e2"));
+        assert_eq!(wrong_rank.validate(), Err(PositionError::InvalidEnPassant));
+
+        // With White to move the pushed pawn would have to be a black one on e5
+        let mut wrong_side = after_double_push.clone();
+        wrong_side.side_to_move = Side::White;
+        assert_eq!(wrong_side.validate(), Err(PositionError::InvalidEnPassant));
+    }
+
+    #[test]
+    fn test_fen_process_strict() {
+        let two_white_kings = Fen("r3k2r/8/8/8/8/8/8/R3K2K w KQkq - 0 1".to_string());
+        assert!(two_white_kings.process().is_ok());
+        assert!(matches!(
+            two_white_kings.process_strict(),
+            Err(FenError::InvalidPosition(PositionError::InvalidKingCount(Side::White, 2)))
+        ));
+    }
+
+    #[test]
+    fn test_fen_process_strict_start_position() {
+        assert!(Fen(DEFAULT_FEN.to_string()).process_strict().is_ok());
+    }
 }
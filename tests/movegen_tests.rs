@@ -1,5 +1,815 @@
 #[cfg(test)]
 mod tests {
-    use rusty_chess_engine::movegen::*;
+    use rusty_chess_engine::gamestate::{board::*, castling_rights::*, Gamestate, Move, MoveFlags};
+    use rusty_chess_engine::utils::fen::{Fen, DEFAULT_FEN};
+    use rusty_chess_engine::movegen::{
+        attacks::{self, SliderBackend},
+        magic_bitboards::{self, magic_numbers, magic_from_number, relevant_occupancy_mask, verify_magic, occupancy_subsets, rook_attacks_on_the_fly, bishop_attacks_on_the_fly},
+        rook_attacks, bishop_attacks, queen_attacks,
+        perft::{perft, perft_staged, divide},
+        MoveGen, MoveList, GenType,
+    };
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
 
-}
\ No newline at end of file
+    fn square(coords: &str) -> Square {
+        Square::new_from_algebraic_notation(coords).unwrap()
+    }
+    fn bitboard(squares: &[&str]) -> Bitboard {
+        squares.iter().fold(0, |acc, coords| acc | square(coords).get_mask())
+    }
+    // Builds a position from a list of pieces given in algebraic notation
+    fn game_from_pieces(pieces: &[(&str, PieceType, Side)], side_to_move: Side) -> Gamestate {
+        game_from_pieces_with_castling(pieces, side_to_move, CastlingRights::new())
+    }
+    fn game_from_pieces_with_castling(pieces: &[(&str, PieceType, Side)], side_to_move: Side, castling_rights: CastlingRights) -> Gamestate {
+        let mut board = Board::default();
+        for &(coords, piece_type, side) in pieces {
+            board.place_piece_at_square(square(coords), piece_type, side);
+        }
+        Gamestate::new(board, side_to_move, castling_rights, None, 0, 1)
+    }
+
+    #[test]
+    fn test_relevant_occupancy_masks() {
+        assert_eq!(relevant_occupancy_mask(PieceType::Rook, square("a1")).count_ones(), 12);
+        assert_eq!(relevant_occupancy_mask(PieceType::Rook, square("d4")).count_ones(), 10);
+        assert_eq!(relevant_occupancy_mask(PieceType::Bishop, square("a1")).count_ones(), 6);
+        assert_eq!(relevant_occupancy_mask(PieceType::Bishop, square("d4")).count_ones(), 9);
+
+        assert_eq!(
+            relevant_occupancy_mask(PieceType::Rook, square("a1")),
+            bitboard(&["a2", "a3", "a4", "a5", "a6", "a7", "b1", "c1", "d1", "e1", "f1", "g1"])
+        );
+    }
+
+    #[test]
+    fn test_generated_magic_numbers_are_valid() {
+        for i in 0..64 {
+            let sq = Square::new(i);
+            let rook_magic = magic_from_number(PieceType::Rook, sq, magic_numbers::ROOK_MAGIC_NUMBERS[i as usize]);
+            let bishop_magic = magic_from_number(PieceType::Bishop, sq, magic_numbers::BISHOP_MAGIC_NUMBERS[i as usize]);
+            assert!(verify_magic(PieceType::Rook, sq, &rook_magic));
+            assert!(verify_magic(PieceType::Bishop, sq, &bishop_magic));
+        }
+    }
+
+    #[test]
+    fn test_slider_attacks_with_blockers() {
+        let occupancy = bitboard(&["d6", "b4", "d2", "g7", "a1"]);
+
+        assert_eq!(
+            rook_attacks(square("d4"), occupancy),
+            bitboard(&["d5", "d6", "d3", "d2", "c4", "b4", "e4", "f4", "g4", "h4"])
+        );
+        assert_eq!(
+            bishop_attacks(square("d4"), occupancy),
+            bitboard(&["e5", "f6", "g7", "c5", "b6", "a7", "e3", "f2", "g1", "c3", "b2", "a1"])
+        );
+        assert_eq!(
+            queen_attacks(square("d4"), occupancy),
+            rook_attacks(square("d4"), occupancy) | bishop_attacks(square("d4"), occupancy)
+        );
+    }
+
+    #[test]
+    fn test_magic_attacks_match_on_the_fly() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for i in 0..64 {
+            let sq = Square::new(i);
+            for _ in 0..200 {
+                let occupancy: Bitboard = rng.gen::<u64>() & rng.gen::<u64>();
+                assert_eq!(rook_attacks(sq, occupancy), rook_attacks_on_the_fly(sq, occupancy));
+                assert_eq!(bishop_attacks(sq, occupancy), bishop_attacks_on_the_fly(sq, occupancy));
+            }
+        }
+    }
+
+    #[test]
+    fn test_slider_backend_detection() {
+        let expected = if cfg!(feature = "force-magic") || !attacks::has_bmi2() {
+            SliderBackend::Magic
+        } else {
+            SliderBackend::Pext
+        };
+        assert_eq!(attacks::slider_backend(), expected);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_pext_and_magic_backends_are_identical() {
+        use rusty_chess_engine::movegen::pext_bitboards;
+        // Nothing to compare with on a CPU without BMI2
+        if !attacks::has_bmi2() {
+            return;
+        }
+
+        let mut rng = StdRng::seed_from_u64(1337);
+        for i in 0..64 {
+            let sq = Square::new(i);
+            let random_occupancies: Vec<Bitboard> = (0..500).map(|_| rng.gen::<u64>() & rng.gen::<u64>()).collect();
+
+            let rook_occupancies = occupancy_subsets(relevant_occupancy_mask(PieceType::Rook, sq)).into_iter().chain(random_occupancies.iter().copied());
+            for occupancy in rook_occupancies {
+                // SAFETY: BMI2 support is checked above
+                let pext = unsafe { pext_bitboards::rook_attacks(sq, occupancy) };
+                assert_eq!(pext, magic_bitboards::rook_attacks(sq, occupancy));
+            }
+            let bishop_occupancies = occupancy_subsets(relevant_occupancy_mask(PieceType::Bishop, sq)).into_iter().chain(random_occupancies.iter().copied());
+            for occupancy in bishop_occupancies {
+                // SAFETY: BMI2 support is checked above
+                let pext = unsafe { pext_bitboards::bishop_attacks(sq, occupancy) };
+                assert_eq!(pext, magic_bitboards::bishop_attacks(sq, occupancy));
+            }
+        }
+    }
+
+    #[test]
+    fn test_slider_moves_generation() {
+        let game = game_from_pieces(&[
+            ("d4", PieceType::Rook, Side::White),
+            ("d6", PieceType::Pawn, Side::Black),
+            ("f4", PieceType::Knight, Side::White),
+            ("b2", PieceType::Bishop, Side::White),
+            ("h8", PieceType::Queen, Side::White),
+            ("e1", PieceType::King, Side::White),
+            ("a5", PieceType::King, Side::Black),
+        ], Side::White);
+
+        let moves = MoveGen::new(&game).gererate();
+        let rook_moves: Vec<_> = moves.iter().filter(|m| m.get_from_square() == square("d4")).collect();
+        // d5, d6(capture), d3, d2, d1, c4, b4, a4, e4
+        assert_eq!(rook_moves.len(), 9);
+        assert_eq!(rook_moves.iter().filter(|m| m.get_flags() == MoveFlags::Capture).count(), 1);
+
+        // Both bishop and queen are blocked by the rook on the long diagonal
+        assert_eq!(moves.iter().filter(|m| m.get_from_square() == square("b2")).count(), 4);
+        assert_eq!(moves.iter().filter(|m| m.get_from_square() == square("h8")).count(), 17);
+    }
+
+    fn moves_from(moves: &[Move], from: &str) -> Vec<String> {
+        let mut targets: Vec<String> = moves.iter()
+            .filter(|m| m.get_from_square() == square(from))
+            .map(|m| m.get_to_square().to_algebraic_notation())
+            .collect();
+        targets.sort();
+        targets
+    }
+
+    #[test]
+    fn test_pinned_piece_moves_only_along_the_pin() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e2", PieceType::Rook, Side::White),
+            ("d2", PieceType::Bishop, Side::White),
+            ("e8", PieceType::Rook, Side::Black),
+            ("a5", PieceType::Bishop, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+        ], Side::White);
+
+        let moves = MoveGen::new(&game).gererate();
+        assert_eq!(moves_from(&moves, "e2"), vec!["e3", "e4", "e5", "e6", "e7", "e8"]);
+        // Bishop is pinned on the diagonal, so it can only go toward the pinning bishop
+        assert_eq!(moves_from(&moves, "d2"), vec!["a5", "b4", "c3"]);
+    }
+
+    #[test]
+    fn test_single_check_evasions() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("c3", PieceType::Knight, Side::White),
+            ("h5", PieceType::Bishop, Side::White),
+            ("e8", PieceType::Rook, Side::Black),
+            ("a8", PieceType::King, Side::Black),
+        ], Side::White);
+
+        let moves = MoveGen::new(&game).gererate();
+        // Knight can only block, bishop can block on e2 or capture the rook
+        assert_eq!(moves_from(&moves, "c3"), vec!["e2", "e4"]);
+        assert_eq!(moves_from(&moves, "h5"), vec!["e2", "e8"]);
+        // King can not step on the e file
+        assert_eq!(moves_from(&moves, "e1"), vec!["d1", "d2", "f1", "f2"]);
+        assert_eq!(moves.len(), 8);
+    }
+
+    #[test]
+    fn test_double_check_allows_only_king_moves() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("c3", PieceType::Knight, Side::White),
+            ("e8", PieceType::Rook, Side::Black),
+            ("d3", PieceType::Knight, Side::Black),
+            ("a8", PieceType::King, Side::Black),
+        ], Side::White);
+
+        let moves = MoveGen::new(&game).gererate();
+        assert!(moves.iter().all(|m| m.get_from_square() == square("e1")));
+        // e2 is on the rook's file and f2 is covered by the knight
+        assert_eq!(moves_from(&moves, "e1"), vec!["d1", "d2", "f1"]);
+    }
+
+    #[test]
+    fn test_king_can_not_capture_protected_piece_or_stay_on_the_checking_line() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e2", PieceType::Queen, Side::Black),
+            ("e7", PieceType::Rook, Side::Black),
+            ("a8", PieceType::King, Side::Black),
+        ], Side::White);
+
+        let moves = MoveGen::new(&game).gererate();
+        assert!(moves.is_empty());
+
+        // Without the rook queen can be taken, but king still can not step back along the check ray
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("c1", PieceType::Rook, Side::Black),
+            ("a8", PieceType::King, Side::Black),
+        ], Side::White);
+        let moves = MoveGen::new(&game).gererate();
+        assert_eq!(moves_from(&moves, "e1"), vec!["d2", "e2", "f2"]);
+    }
+
+    fn castling_moves(game: &Gamestate) -> Vec<(String, MoveFlags)> {
+        let mut moves: Vec<(String, MoveFlags)> = MoveGen::new(game).gererate().iter()
+            .filter(|m| m.is_castle())
+            .map(|m| (m.get_to_square().to_algebraic_notation(), m.get_flags()))
+            .collect();
+        moves.sort_by_key(|(to, _)| to.clone());
+        moves
+    }
+    fn castling_pieces<'a>(extra: &[(&'a str, PieceType, Side)]) -> Vec<(&'a str, PieceType, Side)> {
+        let mut pieces = vec![
+            ("e1", PieceType::King, Side::White),
+            ("a1", PieceType::Rook, Side::White),
+            ("h1", PieceType::Rook, Side::White),
+            ("e8", PieceType::King, Side::Black),
+            ("a8", PieceType::Rook, Side::Black),
+            ("h8", PieceType::Rook, Side::Black),
+        ];
+        pieces.extend_from_slice(extra);
+        pieces
+    }
+    fn rights(white: CastlingSide, black: CastlingSide) -> CastlingRights {
+        let mut rights = CastlingRights::new();
+        rights.set_for_side(Side::White, white);
+        rights.set_for_side(Side::Black, black);
+        rights
+    }
+
+    #[test]
+    fn test_castling_both_sides() {
+        let game = game_from_pieces_with_castling(&castling_pieces(&[]), Side::White, rights(CastlingSide::Both, CastlingSide::Both));
+        assert_eq!(castling_moves(&game), vec![
+            ("c1".to_string(), MoveFlags::QueenCastle),
+            ("g1".to_string(), MoveFlags::KingCastle),
+        ]);
+
+        let game = game_from_pieces_with_castling(&castling_pieces(&[]), Side::Black, rights(CastlingSide::Both, CastlingSide::Both));
+        assert_eq!(castling_moves(&game), vec![
+            ("c8".to_string(), MoveFlags::QueenCastle),
+            ("g8".to_string(), MoveFlags::KingCastle),
+        ]);
+    }
+
+    #[test]
+    fn test_castling_requires_rights() {
+        let game = game_from_pieces_with_castling(&castling_pieces(&[]), Side::White, rights(CastlingSide::Kingside, CastlingSide::Queenside));
+        assert_eq!(castling_moves(&game), vec![("g1".to_string(), MoveFlags::KingCastle)]);
+
+        let game = game_from_pieces_with_castling(&castling_pieces(&[]), Side::Black, rights(CastlingSide::Kingside, CastlingSide::Queenside));
+        assert_eq!(castling_moves(&game), vec![("c8".to_string(), MoveFlags::QueenCastle)]);
+
+        let game = game_from_pieces_with_castling(&castling_pieces(&[]), Side::White, rights(CastlingSide::None, CastlingSide::Both));
+        assert!(castling_moves(&game).is_empty());
+    }
+
+    #[test]
+    fn test_castling_blocked_by_pieces() {
+        // Knight on b1 is not on the king's path, but still blocks the rook
+        let pieces = castling_pieces(&[("b1", PieceType::Knight, Side::White), ("f8", PieceType::Bishop, Side::Black)]);
+        let game = game_from_pieces_with_castling(&pieces, Side::White, rights(CastlingSide::Both, CastlingSide::Both));
+        assert_eq!(castling_moves(&game), vec![("g1".to_string(), MoveFlags::KingCastle)]);
+
+        let game = game_from_pieces_with_castling(&pieces, Side::Black, rights(CastlingSide::Both, CastlingSide::Both));
+        assert_eq!(castling_moves(&game), vec![("c8".to_string(), MoveFlags::QueenCastle)]);
+    }
+
+    #[test]
+    fn test_castling_thru_attacked_squares() {
+        // King is in check
+        let pieces = castling_pieces(&[("e4", PieceType::Rook, Side::Black)]);
+        let game = game_from_pieces_with_castling(&pieces, Side::White, rights(CastlingSide::Both, CastlingSide::Both));
+        assert!(castling_moves(&game).is_empty());
+
+        // f1 is attacked, so only queenside is left
+        let pieces = castling_pieces(&[("f4", PieceType::Rook, Side::Black)]);
+        let game = game_from_pieces_with_castling(&pieces, Side::White, rights(CastlingSide::Both, CastlingSide::Both));
+        assert_eq!(castling_moves(&game), vec![("c1".to_string(), MoveFlags::QueenCastle)]);
+
+        // c8 is the king's destination and is attacked
+        let pieces = castling_pieces(&[("c4", PieceType::Rook, Side::White)]);
+        let game = game_from_pieces_with_castling(&pieces, Side::Black, rights(CastlingSide::Both, CastlingSide::Both));
+        assert_eq!(castling_moves(&game), vec![("g8".to_string(), MoveFlags::KingCastle)]);
+
+        // b1 is attacked, but king does not pass thru it
+        let pieces = castling_pieces(&[("b4", PieceType::Rook, Side::Black)]);
+        let game = game_from_pieces_with_castling(&pieces, Side::White, rights(CastlingSide::Both, CastlingSide::Both));
+        assert_eq!(castling_moves(&game), vec![
+            ("c1".to_string(), MoveFlags::QueenCastle),
+            ("g1".to_string(), MoveFlags::KingCastle),
+        ]);
+    }
+
+    fn moves_with_flags_from(moves: &[Move], from: &str) -> Vec<(String, MoveFlags)> {
+        let mut targets: Vec<(String, MoveFlags)> = moves.iter()
+            .filter(|m| m.get_from_square() == square(from))
+            .map(|m| (m.get_to_square().to_algebraic_notation(), m.get_flags()))
+            .collect();
+        targets.sort_by_key(|(to, flags)| (to.clone(), *flags as u8));
+        targets
+    }
+
+    #[test]
+    fn test_start_position_moves() {
+        let game = Fen(DEFAULT_FEN.to_string()).process().unwrap();
+        let moves = MoveGen::new(&game).gererate();
+        assert_eq!(moves.len(), 20);
+        assert_eq!(moves.iter().filter(|m| m.get_flags() == MoveFlags::DoublePawnPush).count(), 8);
+    }
+
+    #[test]
+    fn test_pawn_pushes_and_blocked_double_push() {
+        let mut game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("a2", PieceType::Pawn, Side::White),
+            ("b2", PieceType::Pawn, Side::White),
+            ("b4", PieceType::Knight, Side::Black),
+            ("c2", PieceType::Pawn, Side::White),
+            ("c3", PieceType::Knight, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+            ("g7", PieceType::Pawn, Side::Black),
+        ], Side::White);
+
+        let moves = MoveGen::new(&game).gererate();
+        assert_eq!(moves_with_flags_from(&moves, "a2"), vec![
+            ("a3".to_string(), MoveFlags::Quiet),
+            ("a4".to_string(), MoveFlags::DoublePawnPush),
+        ]);
+        // Double push is blocked on the destination square, and pawn captures the blocker diagonally
+        assert_eq!(moves_with_flags_from(&moves, "b2"), vec![
+            ("b3".to_string(), MoveFlags::Quiet),
+            ("c3".to_string(), MoveFlags::Capture),
+        ]);
+        // Single push is blocked, so double push is as well
+        assert_eq!(moves_with_flags_from(&moves, "c2"), vec![]);
+
+        game.side_to_move = Side::Black;
+        let moves = MoveGen::new(&game).gererate();
+        assert_eq!(moves_with_flags_from(&moves, "g7"), vec![
+            ("g5".to_string(), MoveFlags::DoublePawnPush),
+            ("g6".to_string(), MoveFlags::Quiet),
+        ]);
+    }
+
+    #[test]
+    fn test_pawn_promotions() {
+        let mut game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("b7", PieceType::Pawn, Side::White),
+            ("a8", PieceType::Rook, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+            ("g2", PieceType::Pawn, Side::Black),
+            ("h1", PieceType::Knight, Side::White),
+        ], Side::White);
+
+        let moves = MoveGen::new(&game).gererate();
+        assert_eq!(moves_with_flags_from(&moves, "b7"), vec![
+            ("a8".to_string(), MoveFlags::KnightPromoCapture),
+            ("a8".to_string(), MoveFlags::BishopPromoCapture),
+            ("a8".to_string(), MoveFlags::RookPromoCapture),
+            ("a8".to_string(), MoveFlags::QueenPromoCapture),
+            ("b8".to_string(), MoveFlags::KnightPromotion),
+            ("b8".to_string(), MoveFlags::BishopPromotion),
+            ("b8".to_string(), MoveFlags::RookPromotion),
+            ("b8".to_string(), MoveFlags::QueenPromotion),
+        ]);
+
+        game.side_to_move = Side::Black;
+        let moves = MoveGen::new(&game).gererate();
+        let black_promotions: Vec<_> = moves.iter().filter(|m| m.get_from_square() == square("g2")).collect();
+        assert_eq!(black_promotions.len(), 8);
+        assert_eq!(black_promotions.iter().filter(|m| m.get_to_square() == square("g1") && m.is_promotion() && !m.is_capture()).count(), 4);
+        assert_eq!(black_promotions.iter().filter(|m| m.get_to_square() == square("h1") && m.is_promo_capture()).count(), 4);
+    }
+
+    #[test]
+    fn test_en_passant_moves() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("d5", PieceType::Pawn, Side::White),
+            ("f5", PieceType::Pawn, Side::White),
+            ("e7", PieceType::Pawn, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+        ], Side::Black);
+
+        let game = game.make_move(&Move::encode(square("e7"), square("e5"), MoveFlags::DoublePawnPush));
+        let moves = MoveGen::new(&game).gererate();
+        let en_passant: Vec<_> = moves.iter().filter(|m| m.get_flags() == MoveFlags::EpCapture).collect();
+        assert_eq!(en_passant.len(), 2);
+        assert!(en_passant.iter().all(|m| m.get_to_square() == square("e6")));
+
+        // Right is lost after any other move
+        let game = game
+            .make_move(&Move::encode(square("e1"), square("e2"), MoveFlags::Quiet))
+            .make_move(&Move::encode(square("h8"), square("h7"), MoveFlags::Quiet));
+        let moves = MoveGen::new(&game).gererate();
+        assert!(moves.iter().all(|m| m.get_flags() != MoveFlags::EpCapture));
+    }
+
+    #[test]
+    fn test_en_passant_not_available() {
+        // Double push next to no enemy pawn sets the square, but there is nothing to capture with
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("b5", PieceType::Pawn, Side::White),
+            ("e7", PieceType::Pawn, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+        ], Side::Black);
+        let game = game.make_move(&Move::encode(square("e7"), square("e5"), MoveFlags::DoublePawnPush));
+        assert_eq!(game.en_passant, Some(square("e6")));
+        assert!(MoveGen::new(&game).gererate().iter().all(|m| m.get_flags() != MoveFlags::EpCapture));
+
+        // Single push next to the pawn gives no en passant at all
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("d5", PieceType::Pawn, Side::White),
+            ("e6", PieceType::Pawn, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+        ], Side::Black);
+        let game = game.make_move(&Move::encode(square("e6"), square("e5"), MoveFlags::Quiet));
+        assert_eq!(game.en_passant, None);
+        assert!(MoveGen::new(&game).gererate().iter().all(|m| m.get_flags() != MoveFlags::EpCapture));
+    }
+
+    #[test]
+    fn test_en_passant_discovered_check_along_the_rank() {
+        let game = game_from_pieces(&[
+            ("a5", PieceType::King, Side::White),
+            ("b5", PieceType::Pawn, Side::White),
+            ("c7", PieceType::Pawn, Side::Black),
+            ("h5", PieceType::Rook, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+        ], Side::Black);
+
+        // Both pawns leave the rank at once, which would expose the king to the rook
+        let game = game.make_move(&Move::encode(square("c7"), square("c5"), MoveFlags::DoublePawnPush));
+        let moves = MoveGen::new(&game).gererate();
+        assert_eq!(moves_with_flags_from(&moves, "b5"), vec![("b6".to_string(), MoveFlags::Quiet)]);
+    }
+
+    #[test]
+    fn test_move_list() {
+        let mut moves = MoveList::new();
+        assert!(moves.is_empty());
+
+        let quiet = Move::encode(square("e2"), square("e3"), MoveFlags::Quiet);
+        let push = Move::encode(square("e2"), square("e4"), MoveFlags::DoublePawnPush);
+        let capture = Move::encode(square("d4"), square("e5"), MoveFlags::Capture);
+        moves.push(quiet);
+        moves.push(push);
+        moves.push(capture);
+        assert_eq!(moves.len(), 3);
+        assert_eq!(moves[1], push);
+
+        moves.retain(|mov| mov.get_flags() != MoveFlags::DoublePawnPush);
+        assert_eq!(&moves[..], &[quiet, capture]);
+
+        moves.clear();
+        assert!(moves.is_empty());
+    }
+
+    // Known positions and node counts are taken from https://www.chessprogramming.org/Perft_Results
+    const KIWIPETE_FEN: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3_FEN: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4_FEN: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_5_FEN: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6_FEN: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    // Expected node counts are given starting from depth 1
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let game = Fen(fen.to_string()).process().unwrap();
+        for (i, &nodes) in expected.iter().enumerate() {
+            assert_eq!(perft(&game, i + 1), nodes, "Perft mismatch for `{}` at depth {}", fen, i + 1);
+            assert_eq!(perft_staged(&game, i + 1), nodes, "Staged perft mismatch for `{}` at depth {}", fen, i + 1);
+        }
+    }
+
+    #[test]
+    fn test_perft_start_position() {
+        assert_perft(DEFAULT_FEN, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn test_perft_kiwipete() {
+        assert_perft(KIWIPETE_FEN, &[48, 2039, 97862, 4085603]);
+    }
+
+    #[test]
+    fn test_perft_position_3() {
+        assert_perft(POSITION_3_FEN, &[14, 191, 2812, 43238]);
+    }
+
+    #[test]
+    fn test_perft_position_4() {
+        assert_perft(POSITION_4_FEN, &[6, 264, 9467, 422333]);
+    }
+
+    #[test]
+    fn test_perft_position_5() {
+        assert_perft(POSITION_5_FEN, &[44, 1486, 62379, 2103487]);
+    }
+
+    #[test]
+    fn test_perft_position_6() {
+        assert_perft(POSITION_6_FEN, &[46, 2079, 89890, 3894594]);
+    }
+
+    // Run with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore = "slow"]
+    fn test_perft_deep() {
+        let deep = [
+            (DEFAULT_FEN, 5, 4865609),
+            (KIWIPETE_FEN, 5, 193690690),
+            (POSITION_3_FEN, 5, 674624),
+            (POSITION_3_FEN, 6, 11030083),
+            (POSITION_4_FEN, 5, 15833292),
+            (POSITION_5_FEN, 5, 89941194),
+            (POSITION_6_FEN, 5, 164075551),
+        ];
+        for (fen, depth, nodes) in deep {
+            let game = Fen(fen.to_string()).process().unwrap();
+            assert_eq!(perft(&game, depth), nodes, "Perft mismatch for `{}` at depth {}", fen, depth);
+        }
+    }
+
+    #[test]
+    fn test_divide() {
+        let game = Fen(DEFAULT_FEN.to_string()).process().unwrap();
+        let divided = divide(&game, 3);
+        assert_eq!(divided.len(), 20);
+        assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), perft(&game, 3));
+
+        let nodes_after = |from: &str, to: &str| divided.iter()
+            .find(|(mov, _)| mov.get_from_square() == square(from) && mov.get_to_square() == square(to))
+            .map(|(_, nodes)| *nodes)
+            .unwrap();
+        assert_eq!(nodes_after("a2", "a3"), 380);
+        assert_eq!(nodes_after("b1", "c3"), 440);
+        assert_eq!(nodes_after("g1", "f3"), 440);
+        // Mirrored moves give different counts, so these catch a board with flipped files
+        assert_eq!(nodes_after("e2", "e4"), 600);
+        assert_eq!(nodes_after("d2", "d4"), 560);
+    }
+
+    fn sorted_moves(moves: &[Move]) -> Vec<(usize, usize, u8)> {
+        let mut moves: Vec<(usize, usize, u8)> = moves.iter()
+            .map(|m| (m.get_from_square().get_index(), m.get_to_square().get_index(), m.get_flags() as u8))
+            .collect();
+        moves.sort();
+        moves
+    }
+    // Walks the whole tree and checks at every node that the stages split the legal moves without gaps or overlaps
+    fn assert_stages_match_all_moves(game: &Gamestate, depth: usize) {
+        let movegen = MoveGen::new(game);
+        let all = movegen.gererate();
+        if game.is_in_check() {
+            assert_eq!(sorted_moves(&movegen.generate_evasions()), sorted_moves(&all));
+        } else {
+            let captures = movegen.generate_captures();
+            let quiets = movegen.generate_quiets();
+            assert!(captures.iter().all(|m| m.is_capture() || m.get_flags() == MoveFlags::QueenPromotion));
+            assert!(quiets.iter().all(|m| !m.is_capture() && m.get_flags() != MoveFlags::QueenPromotion));
+            assert_eq!(sorted_moves(&[&captures[..], &quiets[..]].concat()), sorted_moves(&all));
+        }
+
+        if depth > 1 {
+            for mov in &all {
+                assert_stages_match_all_moves(&game.make_move(mov), depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_staged_generation_matches_all_moves() {
+        assert_stages_match_all_moves(&Fen(DEFAULT_FEN.to_string()).process().unwrap(), 3);
+
+        // Promotions, en passant and checks are rare in the start position tree, so they get a position of their own
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("b7", PieceType::Pawn, Side::White),
+            ("d5", PieceType::Pawn, Side::White),
+            ("f2", PieceType::Pawn, Side::White),
+            ("h3", PieceType::Bishop, Side::White),
+            ("a8", PieceType::Rook, Side::Black),
+            ("e7", PieceType::Pawn, Side::Black),
+            ("g4", PieceType::Pawn, Side::Black),
+            ("c2", PieceType::Knight, Side::Black),
+            ("e8", PieceType::King, Side::Black),
+        ], Side::White);
+        assert_stages_match_all_moves(&game, 3);
+    }
+
+    #[test]
+    fn test_generate_evasions() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("c3", PieceType::Knight, Side::White),
+            ("h5", PieceType::Bishop, Side::White),
+            ("a2", PieceType::Pawn, Side::White),
+            ("e8", PieceType::Rook, Side::Black),
+            ("a8", PieceType::King, Side::Black),
+        ], Side::White);
+
+        let evasions = MoveGen::new(&game).generate_evasions();
+        assert_eq!(moves_from(&evasions, "c3"), vec!["e2", "e4"]);
+        assert_eq!(moves_from(&evasions, "h5"), vec!["e2", "e8"]);
+        assert!(moves_from(&evasions, "a2").is_empty());
+        assert_eq!(moves_from(&evasions, "e1"), vec!["d1", "d2", "f1", "f2"]);
+        assert_eq!(sorted_moves(&evasions), sorted_moves(&MoveGen::new(&game).generate_of_type(GenType::All)));
+    }
+
+    #[test]
+    fn test_generate_captures_promotions() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("b7", PieceType::Pawn, Side::White),
+            ("a8", PieceType::Rook, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+        ], Side::White);
+
+        let movegen = MoveGen::new(&game);
+        assert_eq!(moves_with_flags_from(&movegen.generate_captures(), "b7"), vec![
+            ("a8".to_string(), MoveFlags::KnightPromoCapture),
+            ("a8".to_string(), MoveFlags::BishopPromoCapture),
+            ("a8".to_string(), MoveFlags::RookPromoCapture),
+            ("a8".to_string(), MoveFlags::QueenPromoCapture),
+            ("b8".to_string(), MoveFlags::QueenPromotion),
+        ]);
+        assert_eq!(moves_with_flags_from(&movegen.generate_quiets(), "b7"), vec![
+            ("b8".to_string(), MoveFlags::KnightPromotion),
+            ("b8".to_string(), MoveFlags::BishopPromotion),
+            ("b8".to_string(), MoveFlags::RookPromotion),
+        ]);
+    }
+
+    // Checks gives_check and quiet checks generation against making every move in the whole tree
+    fn assert_checks_match_make_move(game: &Gamestate, depth: usize) {
+        let movegen = MoveGen::new(game);
+        let all = movegen.gererate();
+        let quiets = movegen.generate_quiets();
+
+        let mut expected_quiet_checks: Vec<Move> = Vec::new();
+        for mov in &all {
+            let gives_check = game.make_move(mov).is_in_check();
+            assert_eq!(movegen.gives_check(mov), gives_check, "Wrong check detection for {:?}", mov.decode());
+            // Quiet queen promotions are generated with the captures, but the checking ones belong to quiet checks too
+            let is_quiet = quiets.contains(mov) || mov.get_flags() == MoveFlags::QueenPromotion;
+            if gives_check && is_quiet {
+                expected_quiet_checks.push(*mov);
+            }
+        }
+        assert_eq!(sorted_moves(&movegen.generate_quiet_checks()), sorted_moves(&expected_quiet_checks));
+
+        if depth > 1 {
+            for mov in &all {
+                assert_checks_match_make_move(&game.make_move(mov), depth - 1);
+            }
+        }
+    }
+    // Bishop on a3 is blocked only by the knight, so any knight move discovers a check
+    fn quiet_checks_position() -> Gamestate {
+        game_from_pieces_with_castling(&[
+            ("e1", PieceType::King, Side::White),
+            ("h1", PieceType::Rook, Side::White),
+            ("a3", PieceType::Bishop, Side::White),
+            ("c5", PieceType::Knight, Side::White),
+            ("b7", PieceType::Pawn, Side::White),
+            ("f8", PieceType::King, Side::Black),
+        ], Side::White, rights(CastlingSide::Kingside, CastlingSide::None))
+    }
+
+    #[test]
+    fn test_generate_quiet_checks() {
+        let game = quiet_checks_position();
+        let quiet_checks = MoveGen::new(&game).generate_quiet_checks();
+
+        assert_eq!(moves_from(&quiet_checks, "c5"), vec!["a4", "a6", "b3", "d3", "d7", "e4", "e6"]);
+        assert_eq!(moves_from(&quiet_checks, "h1"), vec!["f1", "h8"]);
+        assert!(moves_from(&quiet_checks, "a3").is_empty());
+        // Castling checks with the rook on f1, promotions check along the eighth rank
+        assert_eq!(moves_with_flags_from(&quiet_checks, "e1"), vec![("g1".to_string(), MoveFlags::KingCastle)]);
+        assert_eq!(moves_with_flags_from(&quiet_checks, "b7"), vec![("b8".to_string(), MoveFlags::RookPromotion), ("b8".to_string(), MoveFlags::QueenPromotion)]);
+        assert_eq!(quiet_checks.len(), 12);
+    }
+
+    #[test]
+    fn test_en_passant_discovered_checks() {
+        let game = game_from_pieces(&[
+            ("a1", PieceType::King, Side::White),
+            ("a2", PieceType::Bishop, Side::White),
+            ("e1", PieceType::Rook, Side::White),
+            ("e5", PieceType::Pawn, Side::White),
+            ("d7", PieceType::Pawn, Side::Black),
+            ("g8", PieceType::King, Side::Black),
+        ], Side::Black);
+        let game = game.make_move(&Move::encode(square("d7"), square("d5"), MoveFlags::DoublePawnPush));
+
+        // Captured pawn opens the diagonal of the bishop
+        let en_passant = Move::encode(square("e5"), square("d6"), MoveFlags::EpCapture);
+        assert!(MoveGen::new(&game).gives_check(&en_passant));
+
+        // Capturing pawn opens the file of the rook
+        let game = game_from_pieces(&[
+            ("a1", PieceType::King, Side::White),
+            ("e1", PieceType::Rook, Side::White),
+            ("e5", PieceType::Pawn, Side::White),
+            ("d7", PieceType::Pawn, Side::Black),
+            ("e8", PieceType::King, Side::Black),
+        ], Side::Black);
+        let game = game.make_move(&Move::encode(square("d7"), square("d5"), MoveFlags::DoublePawnPush));
+        assert!(MoveGen::new(&game).gives_check(&en_passant));
+    }
+
+    #[test]
+    fn test_checks_match_make_move() {
+        assert_checks_match_make_move(&Fen(DEFAULT_FEN.to_string()).process().unwrap(), 3);
+        assert_checks_match_make_move(&quiet_checks_position(), 3);
+    }
+
+    // Every possible 16 bit value has to be legal exactly when the generator produces it
+    fn assert_is_legal_matches_generation(game: &Gamestate) {
+        let movegen = MoveGen::new(game);
+        let all = movegen.gererate();
+        for raw in 0..=u16::MAX {
+            let mov = Move::from_u16(raw);
+            assert_eq!(movegen.is_legal(&mov), all.contains(&mov), "Wrong legality for {:#06x}", raw);
+        }
+        assert!(all.iter().all(|mov| movegen.is_pseudo_legal(mov)));
+    }
+
+    #[test]
+    fn test_is_legal_matches_generation() {
+        assert_is_legal_matches_generation(&Fen(DEFAULT_FEN.to_string()).process().unwrap());
+        assert_is_legal_matches_generation(&quiet_checks_position());
+        assert_is_legal_matches_generation(&game_from_pieces_with_castling(
+            &castling_pieces(&[("f3", PieceType::Bishop, Side::Black), ("b7", PieceType::Pawn, Side::White)]),
+            Side::White,
+            rights(CastlingSide::Both, CastlingSide::Both),
+        ));
+
+        // Pinned piece, en passant and a checking knight
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e2", PieceType::Bishop, Side::White),
+            ("d5", PieceType::Pawn, Side::White),
+            ("e8", PieceType::Rook, Side::Black),
+            ("c7", PieceType::Pawn, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+        ], Side::Black);
+        let game = game.make_move(&Move::encode(square("c7"), square("c5"), MoveFlags::DoublePawnPush));
+        assert_is_legal_matches_generation(&game);
+        let game = game
+            .make_move(&Move::encode(square("e2"), square("d3"), MoveFlags::Quiet))
+            .make_move(&Move::encode(square("h8"), square("g8"), MoveFlags::Quiet));
+        assert!(game.is_in_check());
+        assert_is_legal_matches_generation(&game);
+    }
+
+    #[test]
+    fn test_is_pseudo_legal() {
+        let game = game_from_pieces(&[
+            ("e1", PieceType::King, Side::White),
+            ("e2", PieceType::Bishop, Side::White),
+            ("d4", PieceType::Pawn, Side::White),
+            ("e8", PieceType::Rook, Side::Black),
+            ("h8", PieceType::King, Side::Black),
+        ], Side::White);
+        let movegen = MoveGen::new(&game);
+
+        // Pinned bishop can move by the rules of the piece, but it exposes the king
+        let pinned_move = Move::encode(square("e2"), square("d3"), MoveFlags::Quiet);
+        assert!(movegen.is_pseudo_legal(&pinned_move));
+        assert!(!movegen.is_legal(&pinned_move));
+
+        // Flags have to match the target square and the piece
+        assert!(!movegen.is_pseudo_legal(&Move::encode(square("e2"), square("d3"), MoveFlags::Capture)));
+        assert!(!movegen.is_pseudo_legal(&Move::encode(square("d4"), square("d6"), MoveFlags::DoublePawnPush)));
+        assert!(!movegen.is_pseudo_legal(&Move::encode(square("e1"), square("g1"), MoveFlags::KingCastle)));
+        assert!(!movegen.is_pseudo_legal(&Move::encode(square("h8"), square("g8"), MoveFlags::Quiet)));
+        // Flags 6 and 7 are not used by the encoding
+        assert!(!movegen.is_pseudo_legal(&Move::from_u16(square("d4").get_index() as u16 | (square("d5").get_index() as u16) << 6 | 0b0110 << 12)));
+    }
+}